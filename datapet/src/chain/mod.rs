@@ -1,10 +1,16 @@
-use std::{borrow::Cow, collections::HashMap, fmt::Display, ops::Deref};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    ops::Deref,
+};
 
 use codegen::{Module, Scope};
 use datapet_lang::location::Location;
 use itertools::Itertools;
 use proc_macro2::TokenStream;
 use serde::Deserialize;
+use truc::record::definition::RecordDefinition;
 
 use self::error::ChainError;
 use crate::prelude::*;
@@ -23,6 +29,11 @@ struct ChainThread {
     output_streams: Box<[NodeStream]>,
     input_pipes: Option<Box<[usize]>>,
     output_pipes: Option<Box<[usize]>>,
+    /// The `datapet_pipe` main forwarding the output of the thread to its output pipe, generated
+    /// once the threads are planned.
+    pipe_def: Option<TokenStream>,
+    /// The nodes implemented in the thread, in the order they are generated.
+    nodes: Vec<FullyQualifiedName>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Default, Deserialize, Debug)]
@@ -32,6 +43,143 @@ pub enum ChainThreadType {
     Background,
 }
 
+/// The way the threads of a chain are run.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ChainRuntime {
+    /// Each thread is spawned as an OS thread, threads being connected by `std::sync::mpsc`
+    /// channels.
+    #[default]
+    Threads,
+    /// The chain main is an `async fn` which runs each thread as a blocking tokio task, threads
+    /// being connected by bounded `tokio::sync::mpsc` channels.
+    ///
+    /// Filters are synchronous, so every thread occupies a thread of the blocking pool of the
+    /// runtime until the chain ends. This requires the `tokio` feature of `datapet_support`.
+    Tokio,
+    /// All the threads run to completion one after the other on the thread calling the chain
+    /// main, in the order of the data flow, threads being connected by unbounded in-memory
+    /// queues. Background threads run last, once interrupted.
+    ///
+    /// The streams crossing threads are entirely kept in memory, this is meant for reproducible
+    /// tests and debugging.
+    Sequential,
+}
+
+impl ChainRuntime {
+    /// The module, receiver and sender types of the channels, unless they are the
+    /// `std::sync::mpsc` ones.
+    fn channel_types(self) -> Option<(&'static str, &'static str, &'static str)> {
+        match self {
+            ChainRuntime::Threads => None,
+            ChainRuntime::Tokio => Some((
+                "datapet_support::iterator::sync::tokio",
+                "BlockingReceiver",
+                "BlockingSender",
+            )),
+            ChainRuntime::Sequential => Some((
+                "datapet_support::iterator::sync::queue",
+                "QueueReceiver",
+                "QueueSender",
+            )),
+        }
+    }
+}
+
+/// When the chain checks at runtime that its streams respect their order and distinct facts.
+///
+/// Order facts are checked with the order fields of consecutive records. Distinct facts are
+/// checked with consecutive records as well when the stream is ordered by its distinct fields
+/// first, otherwise every distinct key of the stream is kept in memory. The fields of the facts
+/// must implement `Clone`, `Ord` and `Debug`, and distinct facts including sub-streams are not
+/// checked.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ChainFactsCheck {
+    #[default]
+    Never,
+    /// Only if `debug_assertions` are enabled. The checks are generated all the same and skipped
+    /// at runtime in release builds.
+    DebugBuilds,
+    Always,
+}
+
+/// A typed configuration parameter of a chain, parsed from the configuration variables before
+/// any thread starts and given to the threads in the generated `Config` struct.
+#[derive(Clone, Debug)]
+pub struct ChainConfigParam {
+    pub name: String,
+    /// The Rust type of the parameter, which implements `FromStr`.
+    pub r#type: String,
+    /// The value used when the variable is not set, parsed like the variable would be.
+    pub default: Option<String>,
+    pub doc: Option<String>,
+}
+
+#[derive(Debug)]
+struct ChainPipe {
+    source: NodeStreamSource,
+}
+
+#[derive(Debug)]
+struct ChainExternal {
+    name: String,
+    thread_id: usize,
+    stream: NodeStream,
+}
+
+/// The layout of the threads of a chain once planned, written to `chain_plan.txt` next to the
+/// generated chain.
+///
+/// Threads are not merged: inline nodes already run in the thread of the node they read from,
+/// while a thread node pushes its records into channels, so its consumers cannot run in its
+/// thread.
+#[derive(Debug)]
+pub struct ChainPlan {
+    pub threads: Vec<ChainPlanThread>,
+    /// The pipes between the threads, by id, with the source of the records they carry.
+    pub pipes: Vec<(usize, NodeStreamSource)>,
+}
+
+#[derive(Debug)]
+pub struct ChainPlanThread {
+    pub id: usize,
+    pub name: FullyQualifiedName,
+    pub thread_type: ChainThreadType,
+    pub main: Option<FullyQualifiedName>,
+    pub nodes: Vec<FullyQualifiedName>,
+    pub input_pipes: Vec<usize>,
+    pub output_pipes: Vec<usize>,
+}
+
+impl Display for ChainPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for thread in &self.threads {
+            writeln!(f, "{}", thread)?;
+        }
+        for (pipe, source) in &self.pipes {
+            writeln!(f, "pipe {}: {}", pipe, source)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ChainPlanThread {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "thread {} {} ({:?}): main {}, nodes [{}], input pipes [{}], output pipes [{}]",
+            self.id,
+            self.name,
+            self.thread_type,
+            self.main
+                .as_ref()
+                .map_or_else(|| "none".to_string(), ToString::to_string),
+            self.nodes.iter().join(", "),
+            self.input_pipes.iter().join(", "),
+            self.output_pipes.iter().join(", "),
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct ChainSourceThread {
     pub thread_id: usize,
@@ -62,7 +210,7 @@ impl ChainSourceThread {
             quote! {
                 let #mutable_input input = {
                     let rx = thread_control.#input.take().expect("input {stream_index}");
-                    datapet_support::iterator::sync::mpsc::Receive::<_, #error_type>::new(rx)
+                    datapet_support::iterator::sync::mpsc::Receive::<_, #error_type, _>::new(rx)
                 };
             }
         }
@@ -73,12 +221,23 @@ impl ChainSourceThread {
 pub struct Chain<'a> {
     customizer: &'a ChainCustomizer,
     scope: &'a mut Scope,
+    config_params: &'a [ChainConfigParam],
+    record_definitions: &'a BTreeMap<StreamRecordType, RecordDefinition>,
     #[new(default)]
     threads: Vec<ChainThread>,
     #[new(default)]
     thread_by_source: HashMap<NodeStreamSource, ChainSourceThread>,
     #[new(default)]
-    pipe_count: usize,
+    pipes: Vec<ChainPipe>,
+    #[new(default)]
+    channel_capacity_by_source: HashMap<NodeStreamSource, usize>,
+    #[new(default)]
+    external_inputs: Vec<ChainExternal>,
+    #[new(default)]
+    external_outputs: Vec<ChainExternal>,
+    /// The streams whose facts are already checked by the inline node producing them.
+    #[new(default)]
+    checked_sources: HashSet<NodeStreamSource>,
 }
 
 impl<'a> Chain<'a> {
@@ -141,7 +300,7 @@ impl<'a> Chain<'a> {
             Some(
                 output_streams
                     .iter()
-                    .map(|_| self.new_pipe())
+                    .map(|output_stream| self.new_pipe(output_stream.source()))
                     .collect::<Vec<_>>()
                     .into_boxed_slice(),
             )
@@ -157,12 +316,9 @@ impl<'a> Chain<'a> {
             output_streams,
             input_pipes,
             output_pipes,
+            pipe_def: None,
+            nodes: Vec::new(),
         });
-        let name = format!("thread_{}", thread_id);
-        let module = self.scope.new_module(&name).vis("pub").scope();
-        for (path, ty) in &self.customizer.custom_module_imports {
-            module.import(path, ty);
-        }
         thread_id
     }
 
@@ -248,56 +404,143 @@ impl<'a> Chain<'a> {
         );
     }
 
-    fn new_pipe(&mut self) -> usize {
-        let pipe = self.pipe_count;
-        self.pipe_count = pipe + 1;
+    fn new_pipe(&mut self, source: &NodeStreamSource) -> usize {
+        let pipe = self.pipes.len();
+        self.pipes.push(ChainPipe {
+            source: source.clone(),
+        });
         pipe
     }
 
+    /// Overrides the capacity of the channel which would carry the records of `source` if it
+    /// gets piped to another thread.
+    pub fn set_channel_capacity(&mut self, source: &NodeStreamSource, capacity: usize) {
+        self.channel_capacity_by_source
+            .insert(source.clone(), capacity);
+    }
+
+    /// Overrides the capacity of the channels of all the `outputs` of a node, as set by the
+    /// `channel_capacity` parameter of its filter if any.
+    pub fn set_outputs_channel_capacity(
+        &mut self,
+        outputs: &[NodeStream],
+        capacity: Option<usize>,
+    ) {
+        if let Some(capacity) = capacity {
+            for output in outputs {
+                self.set_channel_capacity(output.source(), capacity);
+            }
+        }
+    }
+
+    /// Registers the external input of a thread, whose records are given to the `start` entry
+    /// point of the chain.
+    pub fn add_external_input(&mut self, thread_id: usize, name: &str, output: &NodeStream) {
+        self.check_new_external(thread_id, name, &self.external_inputs);
+        self.external_inputs.push(ChainExternal {
+            name: name.to_string(),
+            thread_id,
+            stream: output.clone(),
+        });
+    }
+
+    /// Registers the external output of a thread, whose records are returned by the `start`
+    /// entry point of the chain.
+    pub fn add_external_output(&mut self, thread_id: usize, name: &str, input: &NodeStream) {
+        self.check_new_external(thread_id, name, &self.external_outputs);
+        self.external_outputs.push(ChainExternal {
+            name: name.to_string(),
+            thread_id,
+            stream: input.clone(),
+        });
+    }
+
+    fn check_new_external(&self, thread_id: usize, name: &str, same_kind: &[ChainExternal]) {
+        assert!(
+            !self
+                .external_inputs
+                .iter()
+                .chain(&self.external_outputs)
+                .any(|external| external.name == name),
+            r#"External "{}" is declared twice"#,
+            name
+        );
+        assert!(
+            !same_kind
+                .iter()
+                .any(|external| external.thread_id == thread_id),
+            r#"Thread "{}" already has an external of the same kind"#,
+            self.threads[thread_id].name
+        );
+    }
+
     fn pipe_single_thread(&mut self, source: &NodeStreamSource) -> usize {
         let source_thread = self.get_source_thread(source).clone();
         let thread = &mut self.threads[source_thread.thread_id];
         if let Some(output_pipes) = &thread.output_pipes {
             return output_pipes[source_thread.stream_index];
         }
-        let pipe = self.new_pipe();
+        let pipe = self.new_pipe(source);
         let thread = &mut self.threads[source_thread.thread_id];
         let name = format!("thread_{}", source_thread.thread_id);
-        let scope = self
-            .scope
-            .get_module_mut(&name)
-            .expect("thread module")
-            .scope();
-        let mut import_scope = ImportScope::default();
         if thread.output_pipes.is_none() {
             assert_eq!(thread.output_streams.len(), 1);
-            import_scope.add_import("fallible_iterator", "FallibleIterator");
 
             {
                 let error_type = self.customizer.error_type.to_name();
 
                 let input = source_thread.format_input(source, self.customizer, true);
 
-                let pipe_def = quote! {
-                    pub fn datapet_pipe(mut thread_control: ThreadControl) -> impl FnOnce() -> Result<(), #error_type> {
-                        move || {
-                            let tx = thread_control.output_0.take().expect("output 0");
-                            #input
-                            while let Some(record) = input.next()? {
-                                tx.send(Some(record))?;
+                let pipe_main = quote! {
+                    move || {
+                        let tx = thread_control.output_0.take().expect("output 0");
+                        let cancellation = thread_control.cancellation.clone();
+                        #input
+                        while let Some(record) = input.next()? {
+                            if cancellation.is_cancelled() {
+                                // Another thread failed, which is the error reported
+                                return Ok(());
                             }
-                            tx.send(None)?;
-                            Ok(())
+                            tx.send(Some(record))?;
                         }
+                        tx.send(None)?;
+                        Ok(())
                     }
                 };
-                scope.raw(&pipe_def.to_string());
+
+                let pipe_name = format!("{}::datapet_pipe", source);
+
+                let pipe_main = if self.customizer.memory_groups {
+                    quote! {
+                        let pipe_main = { #pipe_main };
+                        move || crate::dtpt_monitor::run_in_group(#pipe_name, pipe_main)
+                    }
+                } else {
+                    pipe_main
+                };
+
+                let pipe_main = if self.customizer.tracing {
+                    let thread_id = source_thread.thread_id;
+                    quote! {
+                        let span = datapet_support::tracing::info_span!(#pipe_name, thread_id = #thread_id);
+                        let pipe_main = { #pipe_main };
+                        move || datapet_support::chain::tracing::run_traced(span, pipe_main)
+                    }
+                } else {
+                    pipe_main
+                };
+
+                let pipe_def = quote! {
+                    pub fn datapet_pipe(mut thread_control: ThreadControl) -> impl FnOnce() -> Result<(), #error_type> {
+                        #pipe_main
+                    }
+                };
+                thread.pipe_def = Some(pipe_def);
             }
 
             thread.output_pipes = Some(Box::new([pipe]));
             thread.main = Some(FullyQualifiedName::new(name).sub("datapet_pipe"));
         }
-        import_scope.import(scope, self.customizer);
         pipe
     }
 
@@ -326,29 +569,124 @@ impl<'a> Chain<'a> {
         self.threads[thread_id].main = Some(main);
     }
 
-    pub fn gen_chain(&mut self) {
+    /// The layout of the threads and pipes which are about to be generated.
+    fn plan_threads(&self) -> ChainPlan {
+        let plan_thread = |thread: &ChainThread| ChainPlanThread {
+            id: thread.id,
+            name: thread.name.clone(),
+            thread_type: thread.thread_type,
+            main: thread.main.clone(),
+            nodes: thread.nodes.clone(),
+            input_pipes: thread
+                .input_pipes
+                .iter()
+                .flat_map(|pipes| pipes.iter().copied())
+                .collect(),
+            output_pipes: thread
+                .output_pipes
+                .iter()
+                .flat_map(|pipes| pipes.iter().copied())
+                .collect(),
+        };
+        ChainPlan {
+            threads: self.threads.iter().map(plan_thread).collect(),
+            pipes: self
+                .pipes
+                .iter()
+                .enumerate()
+                .map(|(index, pipe)| (index, pipe.source.clone()))
+                .collect(),
+        }
+    }
+
+    pub fn gen_chain(&mut self) -> ChainPlan {
+        let plan = self.plan_threads();
+
         for thread in &self.threads {
+            let input_facts_checked = thread
+                .input_streams
+                .iter()
+                .map(|input_stream| self.receiver_facts_check(input_stream).is_some())
+                .collect::<Vec<bool>>();
             let name = format!("thread_{}", thread.id);
-            let scope = self
-                .scope
-                .get_module_mut(&name)
-                .expect("thread module")
-                .scope();
+            let scope = self.scope.new_module(&name).vis("pub").scope();
+            for (path, ty) in &self.customizer.custom_module_imports {
+                scope.import(path, ty);
+            }
+            if let Some(pipe_def) = &thread.pipe_def {
+                scope.import("fallible_iterator", "FallibleIterator");
+                scope.raw(&pipe_def.to_string());
+            }
             scope.import("std::sync", "Arc");
             scope.import(
                 "datapet_support::chain::configuration",
                 "ChainConfiguration",
             );
+            scope.import("datapet_support::chain::cancellation", "Cancellation");
+            scope.import(
+                &self.customizer.error_type_path(),
+                &self.customizer.error_type_name(),
+            );
+            let batched = self.customizer.pipe_batch_size.is_some();
+            let metered = self.customizer.stream_metrics;
+            let channel_types = self.customizer.runtime.channel_types();
+            let has_channels = !thread.input_streams.is_empty()
+                || (thread.output_pipes.is_some() && !thread.output_streams.is_empty());
+            if metered && has_channels {
+                scope.import("datapet_support::chain::metrics", "Metered");
+            }
             if thread.input_streams.len() > 0 {
-                scope.import("std::sync::mpsc", "Receiver");
+                if batched {
+                    scope.import("datapet_support::iterator::sync::mpsc", "BatchReceiver");
+                }
+                if let Some((path, receiver, _)) = channel_types {
+                    scope.import(path, receiver);
+                } else {
+                    scope.import("std::sync::mpsc", "Receiver");
+                }
             }
             if thread.output_pipes.is_some() && thread.output_streams.len() > 0 {
-                scope.import("std::sync::mpsc", "SyncSender");
+                if batched {
+                    scope.import("datapet_support::iterator::sync::mpsc", "BatchSender");
+                }
+                if let Some((path, _, sender)) = channel_types {
+                    scope.import(path, sender);
+                } else {
+                    scope.import("std::sync::mpsc", "SyncSender");
+                }
             }
+            let error_type = self.customizer.error_type.to_name();
+            let meter = |channel: TokenStream| {
+                if metered {
+                    quote! { Metered<#channel> }
+                } else {
+                    channel
+                }
+            };
             let inputs = (0..thread.input_streams.len()).map(|i| format_ident!("input_{}", i));
-            let input_types = thread.input_streams.iter().map(|input_stream| {
+            let input_types = thread.input_streams.iter().zip(input_facts_checked).map(|(input_stream, facts_checked)| {
                 let def = input_stream.definition_fragments(&self.customizer.streams_module_name);
-                def.record()
+                let record = def.record();
+                let receiver = match channel_types {
+                    None => format_ident!("Receiver"),
+                    Some((_, receiver, _)) => format_ident!("{}", receiver),
+                };
+                let cancellable = |rx: TokenStream| quote! {
+                    datapet_support::chain::cancellation::CancellableReceiver<#rx, #error_type>
+                };
+                let rx = if batched {
+                    let rx = cancellable(meter(quote! { #receiver<Vec<#record>> }));
+                    quote! { BatchReceiver<#record, #rx> }
+                } else {
+                    cancellable(meter(quote! { #receiver<Option<#record>> }))
+                };
+                if facts_checked {
+                    quote! {
+                        datapet_support::chain::facts::FactsCheckReceiver<#rx, #record, #error_type>
+                    }
+                } else {
+                    rx
+                }
             });
             let outputs = if thread.output_pipes.is_some() {
                 Some((0..thread.output_streams.len()).map(|i| format_ident!("output_{}", i)))
@@ -361,7 +699,20 @@ impl<'a> Chain<'a> {
                 Some(thread.output_streams.iter().map(|output_stream| {
                     let def =
                         output_stream.definition_fragments(&self.customizer.streams_module_name);
-                    def.record()
+                    let record = def.record();
+                    let sender = match channel_types {
+                        None => format_ident!("SyncSender"),
+                        Some((_, _, sender)) => format_ident!("{}", sender),
+                    };
+                    let cancellable = |tx: TokenStream| quote! {
+                        datapet_support::chain::cancellation::CancellableSender<#tx, #error_type>
+                    };
+                    if batched {
+                        let tx = cancellable(meter(quote! { #sender<Vec<#record>> }));
+                        quote! { BatchSender<#record, #tx> }
+                    } else {
+                        cancellable(meter(quote! { #sender<Option<#record>> }))
+                    }
                 }))
             } else {
                 None
@@ -374,6 +725,47 @@ impl<'a> Chain<'a> {
                     pub interrupt: std::sync::Arc<(std::sync::Mutex<bool>, std::sync::Condvar)>,
                 }),
             };
+            let external_input = self
+                .external_inputs
+                .iter()
+                .find(|external| external.thread_id == thread.id)
+                .map(|external| {
+                    let def = external
+                        .stream
+                        .definition_fragments(&self.customizer.streams_module_name);
+                    let record = def.record();
+                    quote! {
+                        pub external_input: Option<
+                            datapet_support::chain::external::ExternalInput<#record, #error_type>,
+                        >,
+                    }
+                });
+            let external_output = self
+                .external_outputs
+                .iter()
+                .find(|external| external.thread_id == thread.id)
+                .map(|external| {
+                    let def = external
+                        .stream
+                        .definition_fragments(&self.customizer.streams_module_name);
+                    let record = def.record();
+                    let sender = match self.customizer.runtime {
+                        ChainRuntime::Threads | ChainRuntime::Tokio => {
+                            quote! { std::sync::mpsc::SyncSender }
+                        }
+                        ChainRuntime::Sequential => {
+                            quote! { datapet_support::iterator::sync::queue::QueueSender }
+                        }
+                    };
+                    quote! {
+                        pub external_output: Option<#sender<Option<#record>>>,
+                    }
+                });
+            let config = (!self.config_params.is_empty()).then(|| {
+                quote! {
+                    pub config: Arc<super::Config>,
+                }
+            });
             let struct_def = quote! {
 
                 pub struct ThreadOuterControl {
@@ -382,9 +774,13 @@ impl<'a> Chain<'a> {
 
                 pub struct ThreadControl {
                     pub chain_configuration: Arc<ChainConfiguration>,
+                    #config
+                    pub cancellation: Arc<Cancellation<#error_type>>,
                     #interrupt
-                    #(pub #inputs: Option<Receiver<Option<#input_types>>>,)*
-                    #(pub #outputs: Option<SyncSender<Option<#output_types>>>,)*
+                    #(pub #inputs: Option<#input_types>,)*
+                    #(pub #outputs: Option<#output_types>,)*
+                    #external_input
+                    #external_output
                 }
 
             };
@@ -393,14 +789,95 @@ impl<'a> Chain<'a> {
 
         {
             let error_type = self.customizer.error_type.to_name();
+            let runtime = self.customizer.runtime;
 
-            let channels = (0..self.pipe_count).map(|pipe| {
-                let tx = format_ident!("tx_{}", pipe);
-                let rx = format_ident!("rx_{}", pipe);
-                quote! {
-                    let (#tx, #rx) = std::sync::mpsc::sync_channel(42);
-                }
-            });
+            let channels = self
+                .pipes
+                .iter()
+                .enumerate()
+                .map(|(pipe, ChainPipe { source })| {
+                    let tx = format_ident!("tx_{}", pipe);
+                    let rx = format_ident!("rx_{}", pipe);
+                    let source_name = source.to_string();
+                    let capacity = self
+                        .channel_capacity_by_source
+                        .get(source)
+                        .copied()
+                        .unwrap_or(self.customizer.channel_capacity);
+                    let meter = self.customizer.stream_metrics.then(|| {
+                        quote! {
+                            let metrics = chain_configuration.metrics.channel_metrics(
+                                #source_name,
+                                chain_configuration.channel_capacity(#source_name, #capacity),
+                            );
+                            let #tx = datapet_support::chain::metrics::Metered::new(#tx, metrics.clone());
+                            let #rx = datapet_support::chain::metrics::Metered::new(#rx, metrics);
+                        }
+                    });
+                    let has_pipe = |pipes: Option<&[usize]>| {
+                        pipes.map_or(false, |pipes| pipes.contains(&pipe))
+                    };
+                    let sender_thread_id = self
+                        .threads
+                        .iter()
+                        .find(|thread| has_pipe(thread.output_pipes.as_deref()))
+                        .expect("sender thread")
+                        .id;
+                    let receiver_thread_id = self
+                        .threads
+                        .iter()
+                        .find(|thread| has_pipe(thread.input_pipes.as_deref()))
+                        .expect("receiver thread")
+                        .id;
+                    let batch = self.customizer.pipe_batch_size.map(|batch_size| {
+                        quote! {
+                            let #tx = datapet_support::iterator::sync::mpsc::BatchSender::new(#tx, #batch_size);
+                            let #rx = datapet_support::iterator::sync::mpsc::BatchReceiver::new(#rx);
+                        }
+                    });
+                    let channel = match runtime {
+                        ChainRuntime::Threads => quote! {
+                            std::sync::mpsc::sync_channel(
+                                chain_configuration.channel_capacity(#source_name, #capacity),
+                            )
+                        },
+                        ChainRuntime::Tokio => quote! {
+                            datapet_support::iterator::sync::tokio::channel(
+                                chain_configuration.channel_capacity(#source_name, #capacity),
+                            )
+                        },
+                        ChainRuntime::Sequential => quote! {
+                            datapet_support::iterator::sync::queue::channel()
+                        },
+                    };
+                    quote! {
+                        let (#tx, #rx) = #channel;
+                        #meter
+                        let #tx = datapet_support::chain::cancellation::CancellableSender::new(
+                            #tx,
+                            cancellation.clone(),
+                            #sender_thread_id,
+                        );
+                        let #rx = datapet_support::chain::cancellation::CancellableReceiver::new(
+                            #rx,
+                            cancellation.clone(),
+                            #receiver_thread_id,
+                        );
+                        #batch
+                    }
+                });
+
+            let input_facts_checks = self
+                .threads
+                .iter()
+                .map(|thread| {
+                    thread
+                        .input_streams
+                        .iter()
+                        .map(|input_stream| self.receiver_facts_check(input_stream))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
 
             let thread_controls = self
                 .threads
@@ -431,6 +908,8 @@ impl<'a> Chain<'a> {
                             interrupt: #thread_outer_control.interrupt.clone(),
                         }
                     });
+                    let thread_id = thread.id;
+                    let thread_name = thread.name.to_string();
                     let inputs = thread
                         .input_pipes
                         .as_ref()
@@ -438,7 +917,21 @@ impl<'a> Chain<'a> {
                             input_pipes.iter().enumerate().map(|(index, pipe)| {
                                 let input = format_ident!("input_{}", index);
                                 let rx = format_ident!("rx_{}", pipe);
-                                quote! { #input: Some(#rx), }
+                                if let Some(check) = &input_facts_checks[thread_index][index] {
+                                    quote! {
+                                        #input: Some(
+                                            datapet_support::chain::facts::FactsCheckReceiver::new(
+                                                #rx,
+                                                #check,
+                                                cancellation.clone(),
+                                                #thread_id,
+                                                #thread_name,
+                                            ),
+                                        ),
+                                    }
+                                } else {
+                                    quote! { #input: Some(#rx), }
+                                }
                             })
                         })
                         .into_iter()
@@ -464,15 +957,40 @@ impl<'a> Chain<'a> {
                             chain_configuration,
                         }
                     };
+                    let typed_config_assignment = (!self.config_params.is_empty()).then(|| {
+                        quote! {
+                            config: config.clone(),
+                        }
+                    });
+                    let external_input = self
+                        .external_inputs
+                        .iter()
+                        .find(|external| external.thread_id == thread.id)
+                        .map(|external| {
+                            let name = format_ident!("{}", external.name);
+                            quote! { external_input: Some(external_inputs.#name), }
+                        });
+                    let external_output = self
+                        .external_outputs
+                        .iter()
+                        .position(|external| external.thread_id == thread.id)
+                        .map(|index| {
+                            let tx = format_ident!("external_tx_{}", index);
+                            quote! { external_output: Some(#tx), }
+                        });
                     quote! {
                         let #thread_outer_control = #thread_module::ThreadOuterControl {
                             #interrupt
                         };
                         let #thread_control = #thread_module::ThreadControl {
                             #config_assignment
+                            #typed_config_assignment
+                            cancellation: cancellation.clone(),
                             #interrupt_clone
                             #(#inputs)*
                             #(#outputs)*
+                            #external_input
+                            #external_output
                         };
                     }
                 });
@@ -483,11 +1001,29 @@ impl<'a> Chain<'a> {
                     syn::parse_str::<syn::Expr>(&thread.main.as_ref().expect("main").to_string())
                         .expect("thread_main");
                 let thread_control = format_ident!("thread_control_{}", thread.id);
+                let thread_id = thread.id;
+                let thread_name = thread.name.to_string();
+                let spawn = match runtime {
+                    ChainRuntime::Threads => quote! { std::thread::spawn },
+                    ChainRuntime::Tokio => {
+                        quote! { datapet_support::iterator::sync::tokio::spawn_blocking }
+                    }
+                    ChainRuntime::Sequential => unreachable!("sequential threads are not spawned"),
+                };
                 quote! {
-                    let #join_thread = std::thread::spawn(#thread_main(#thread_control));
+                    let #join_thread = {
+                        let cancellation = cancellation.clone();
+                        let thread_main = #thread_main(#thread_control);
+                        #spawn(move || cancellation.run(#thread_id, #thread_name, thread_main))
+                    };
                 }
             });
 
+            let join = match runtime {
+                ChainRuntime::Threads | ChainRuntime::Sequential => quote! { join() },
+                ChainRuntime::Tokio => quote! { await },
+            };
+
             let join_regular_threads = self
                 .threads
                 .iter()
@@ -495,7 +1031,18 @@ impl<'a> Chain<'a> {
                 .map(|thread| {
                     let join_thread = format_ident!("join_{}", thread.id);
                     quote! {
-                        #join_thread.join().unwrap()?;
+                        #join_thread.#join.expect("thread");
+                    }
+                });
+
+            let register_background_threads = self
+                .threads
+                .iter()
+                .filter(|thread| thread.thread_type == ChainThreadType::Background)
+                .map(|thread| {
+                    let thread_outer_control = format_ident!("thread_outer_control_{}", thread.id);
+                    quote! {
+                        cancellation.add_interrupt(#thread_outer_control.interrupt.clone());
                     }
                 });
 
@@ -519,7 +1066,7 @@ impl<'a> Chain<'a> {
                 .map(|thread| {
                     let join_thread = format_ident!("join_{}", thread.id);
                     quote! {
-                        #join_thread.join().unwrap()?;
+                        #join_thread.#join.expect("thread");
                     }
                 });
 
@@ -541,16 +1088,51 @@ impl<'a> Chain<'a> {
                 quote! {}
             };
             let main_name = format_ident!("{}", &self.customizer.main_name);
-            let main_def = quote! {
-                #main_attrs
-                pub fn #main_name(chain_configuration: ChainConfiguration) -> Result<(), #error_type> {
-                    #[allow(unused_variables)]
-                    let chain_configuration = Arc::new(chain_configuration);
+            let asyncness = match runtime {
+                ChainRuntime::Threads | ChainRuntime::Sequential => None,
+                ChainRuntime::Tokio => Some(quote! { async }),
+            };
 
-                    #(#channels)*
+            let run_threads = if runtime == ChainRuntime::Sequential {
+                self.check_sequential_order();
+                let run_thread = |thread: &ChainThread| {
+                    let thread_main = syn::parse_str::<syn::Expr>(
+                        &thread.main.as_ref().expect("main").to_string(),
+                    )
+                    .expect("thread_main");
+                    let thread_control = format_ident!("thread_control_{}", thread.id);
+                    let thread_id = thread.id;
+                    let thread_name = thread.name.to_string();
+                    quote! {
+                        cancellation.run(#thread_id, #thread_name, #thread_main(#thread_control));
+                    }
+                };
+                let run_regular_threads = self
+                    .threads
+                    .iter()
+                    .filter(|thread| thread.thread_type == ChainThreadType::Regular)
+                    .map(|thread| {
+                        let run_thread = run_thread(thread);
+                        quote! {
+                            if !cancellation.is_cancelled() {
+                                #run_thread
+                            }
+                        }
+                    });
+                let run_background_threads = self
+                    .threads
+                    .iter()
+                    .filter(|thread| thread.thread_type == ChainThreadType::Background)
+                    .map(&run_thread);
+                quote! {
+                    #(#run_regular_threads)*
 
-                    #(#thread_controls)*
+                    #(#interrupt_background_threads)*
 
+                    #(#run_background_threads)*
+                }
+            } else {
+                quote! {
                     #(#spawn_threads)*
 
                     #(#join_regular_threads)*
@@ -558,12 +1140,362 @@ impl<'a> Chain<'a> {
                     #(#interrupt_background_threads)*
 
                     #(#join_background_threads)*
+                }
+            };
+            let (install_signal_handler, interrupted) = if self.customizer.handle_signals {
+                (
+                    Some(quote! {
+                        let _signal_guard = datapet_support::chain::signal::SignalGuard::install(
+                            cancellation.clone(),
+                        )
+                        .expect("signal handler");
+                    }),
+                    Some(quote! {
+                        None if cancellation.is_interrupted() => Err(
+                            datapet_support::chain::cancellation::Interrupted.into(),
+                        ),
+                    }),
+                )
+            } else {
+                (None, None)
+            };
+            let thread_failed = if self.customizer.error_context {
+                quote! {
+                    <#error_type as datapet_support::chain::context::ErrorContext>::thread_failed(failure)
+                }
+            } else {
+                // The panic of a thread still names it
+                quote! { failure.into_error(#error_type::custom) }
+            };
+            let result = quote! {
+                match cancellation.take_failure() {
+                    Some(failure) => Err(#thread_failed),
+                    #interrupted
+                    None => Ok(()),
+                }
+            };
+
+            let config_def = (!self.config_params.is_empty()).then(|| {
+                let names = self
+                    .config_params
+                    .iter()
+                    .map(|param| format_ident!("{}", param.name))
+                    .collect::<Vec<_>>();
+                let name_strs = self
+                    .config_params
+                    .iter()
+                    .map(|param| &param.name)
+                    .collect::<Vec<_>>();
+                let types = self.config_params.iter().map(|param| {
+                    syn::parse_str::<syn::Type>(&param.r#type).expect("config type")
+                });
+                let docs = self.config_params.iter().map(|param| {
+                    param.doc.as_ref().map(|doc| {
+                        quote! { #[doc = #doc] }
+                    })
+                });
+                let defaults = self.config_params.iter().map(|param| match &param.default {
+                    Some(default) => quote! { Some(#default) },
+                    None => quote! { None },
+                });
+                quote! {
+                    pub const CONFIG_NAMES: &[&str] = &[#(#name_strs),*];
+
+                    pub struct Config {
+                        #(
+                            #docs
+                            pub #names: #types,
+                        )*
+                    }
+
+                    impl Config {
+                        pub fn from_configuration(
+                            chain_configuration: &ChainConfiguration,
+                        ) -> Result<Self, datapet_support::chain::configuration::ConfigError> {
+                            Ok(Self {
+                                #(
+                                    #names: chain_configuration.parse_variable(#name_strs, #defaults)?,
+                                )*
+                            })
+                        }
+                    }
+
+                    pub fn configuration_from_args<I>(
+                        args: I,
+                    ) -> Result<ChainConfiguration, datapet_support::chain::configuration::ConfigError>
+                    where
+                        I: IntoIterator<Item = String>,
+                    {
+                        ChainConfiguration::from_args(args, CONFIG_NAMES)
+                    }
+
+                    pub fn configuration_from_env(prefix: &str) -> ChainConfiguration {
+                        ChainConfiguration::from_env(prefix, CONFIG_NAMES)
+                    }
+                }
+            });
+            if let Some(config_def) = &config_def {
+                self.scope.raw(&config_def.to_string());
+            }
+
+            let main_def = if self.external_inputs.is_empty() && self.external_outputs.is_empty() {
+                let config_init = config_def.is_some().then(|| {
+                    quote! {
+                        #[allow(unused_variables)]
+                        let config = Arc::new(Config::from_configuration(&chain_configuration)?);
+                    }
+                });
+                quote! {
+                    #main_attrs
+                    pub #asyncness fn #main_name(chain_configuration: ChainConfiguration) -> Result<(), #error_type> {
+                        #config_init
+
+                        #[allow(unused_variables)]
+                        let chain_configuration = Arc::new(chain_configuration);
+
+                        let cancellation = Arc::new(
+                            datapet_support::chain::cancellation::Cancellation::<#error_type>::new(),
+                        );
+
+                        #(#channels)*
+
+                        #(#thread_controls)*
+
+                        #(#register_background_threads)*
+
+                        #install_signal_handler
 
-                    Ok(())
+                        #run_threads
+
+                        #result
+                    }
+                }
+            } else {
+                assert!(
+                    runtime != ChainRuntime::Tokio,
+                    "External inputs and outputs are not supported by the tokio runtime"
+                );
+
+                let external_records = self
+                    .external_inputs
+                    .iter()
+                    .chain(&self.external_outputs)
+                    .map(|external| {
+                        let name = format_ident!("{}", external.name);
+                        let def = external
+                            .stream
+                            .definition_fragments(&self.customizer.streams_module_name);
+                        let record = def.record();
+                        let unpacked_record = def.unpacked_record();
+                        quote! {
+                            pub mod #name {
+                                pub type Record = #record;
+                                pub type UnpackedRecord = #unpacked_record;
+                            }
+                        }
+                    });
+
+                let input_names = self
+                    .external_inputs
+                    .iter()
+                    .map(|external| format_ident!("{}", external.name))
+                    .collect::<Vec<_>>();
+
+                let output_names = self
+                    .external_outputs
+                    .iter()
+                    .map(|external| format_ident!("{}", external.name))
+                    .collect::<Vec<_>>();
+                let output_types = output_names.iter().map(|name| match runtime {
+                    ChainRuntime::Threads | ChainRuntime::Tokio => quote! {
+                        datapet_support::chain::external::ExternalOutput<
+                            external::#name::Record,
+                            #error_type,
+                        >
+                    },
+                    ChainRuntime::Sequential => quote! {
+                        datapet_support::chain::external::ExternalOutput<
+                            external::#name::Record,
+                            #error_type,
+                            datapet_support::iterator::sync::queue::QueueReceiver<
+                                Option<external::#name::Record>,
+                            >,
+                        >
+                    },
+                });
+                let output_rxs = (0..output_names.len())
+                    .map(|index| format_ident!("external_rx_{}", index))
+                    .collect::<Vec<_>>();
+
+                let external_channels = self.external_outputs.iter().enumerate().map(
+                    |(index, ChainExternal { stream, .. })| {
+                        let tx = format_ident!("external_tx_{}", index);
+                        let rx = format_ident!("external_rx_{}", index);
+                        let source_name = stream.source().to_string();
+                        let capacity = self.customizer.channel_capacity;
+                        let channel = match runtime {
+                            ChainRuntime::Threads | ChainRuntime::Tokio => quote! {
+                                std::sync::mpsc::sync_channel(
+                                    chain_configuration.channel_capacity(#source_name, #capacity),
+                                )
+                            },
+                            ChainRuntime::Sequential => quote! {
+                                datapet_support::iterator::sync::queue::channel()
+                            },
+                        };
+                        quote! {
+                            let (#tx, #rx) = #channel;
+                        }
+                    },
+                );
+
+                let external_outputs = quote! {
+                    ExternalOutputs {
+                        #(
+                            #output_names:
+                                datapet_support::iterator::sync::mpsc::Receive::new(#output_rxs),
+                        )*
+                    }
+                };
+
+                // The external outputs are returned along with the error, their senders being
+                // dropped
+                let config_init = config_def.is_some().then(|| {
+                    quote! {
+                        #[allow(unused_variables)]
+                        let config = match Config::from_configuration(&chain_configuration) {
+                            Ok(config) => Arc::new(config),
+                            Err(err) => {
+                                return (
+                                    #external_outputs,
+                                    datapet_support::chain::external::ChainRun::done(Err(err.into())),
+                                );
+                            }
+                        };
+                    }
+                });
+
+                let run = match runtime {
+                    ChainRuntime::Threads | ChainRuntime::Tokio => quote! {
+                        datapet_support::chain::external::ChainRun::spawn(move || {
+                            #install_signal_handler
+
+                            #run_threads
+
+                            #result
+                        })
+                    },
+                    ChainRuntime::Sequential => quote! {{
+                        #install_signal_handler
+
+                        #run_threads
+
+                        datapet_support::chain::external::ChainRun::done(#result)
+                    }},
+                };
+
+                quote! {
+                    pub mod external {
+                        #(#external_records)*
+                    }
+
+                    pub struct ExternalInputs {
+                        #(
+                            pub #input_names: datapet_support::chain::external::ExternalInput<
+                                external::#input_names::Record,
+                                #error_type,
+                            >,
+                        )*
+                    }
+
+                    impl Default for ExternalInputs {
+                        fn default() -> Self {
+                            Self {
+                                #(
+                                    #input_names: Box::new(fallible_iterator::empty::<
+                                        external::#input_names::Record,
+                                        #error_type,
+                                    >()),
+                                )*
+                            }
+                        }
+                    }
+
+                    pub struct ExternalOutputs {
+                        #(pub #output_names: #output_types,)*
+                    }
+
+                    pub fn start(
+                        chain_configuration: ChainConfiguration,
+                        external_inputs: ExternalInputs,
+                    ) -> (
+                        ExternalOutputs,
+                        datapet_support::chain::external::ChainRun<#error_type>,
+                    ) {
+                        #(#external_channels)*
+
+                        #config_init
+
+                        #[allow(unused_variables)]
+                        let chain_configuration = Arc::new(chain_configuration);
+
+                        let cancellation = Arc::new(
+                            datapet_support::chain::cancellation::Cancellation::<#error_type>::new(),
+                        );
+
+                        #(#channels)*
+
+                        #(#thread_controls)*
+
+                        #(#register_background_threads)*
+
+                        let run = #run;
+
+                        (#external_outputs, run)
+                    }
+
+                    #main_attrs
+                    pub fn #main_name(chain_configuration: ChainConfiguration) -> Result<(), #error_type> {
+                        let (external_outputs, run) = start(chain_configuration, ExternalInputs::default());
+                        // Dropping the external outputs discards their records
+                        drop(external_outputs);
+                        run.wait()
+                    }
                 }
             };
             self.scope.raw(&main_def.to_string());
         }
+
+        plan
+    }
+
+    /// Checks that running the regular threads in the order of their creation, then the
+    /// background ones, runs the producer of each pipe before its consumer.
+    fn check_sequential_order(&self) {
+        let rank =
+            |thread: &ChainThread| (thread.thread_type == ChainThreadType::Background, thread.id);
+        let producers = self
+            .threads
+            .iter()
+            .flat_map(|thread| {
+                thread
+                    .output_pipes
+                    .iter()
+                    .flat_map(|pipes| pipes.iter())
+                    .map(move |pipe| (*pipe, thread))
+            })
+            .collect::<HashMap<usize, &ChainThread>>();
+        for thread in &self.threads {
+            for pipe in thread.input_pipes.iter().flat_map(|pipes| pipes.iter()) {
+                let producer = producers[pipe];
+                assert!(
+                    rank(producer) < rank(thread),
+                    r#"Thread "{}" feeds thread "{}" which would run before it sequentially"#,
+                    producer.name,
+                    thread.name,
+                );
+            }
+        }
     }
 
     fn get_or_new_module_scope<'i>(
@@ -598,6 +1530,82 @@ impl<'a> Chain<'a> {
         }
     }
 
+    /// The expression of the optional `datapet_support::chain::facts::RecordCheck` of the facts
+    /// of a stream, if they are checked.
+    fn facts_check(&self, stream: &NodeStream) -> Option<TokenStream> {
+        let enabled = match self.customizer.facts_check {
+            ChainFactsCheck::Never => return None,
+            ChainFactsCheck::DebugBuilds => quote! { cfg!(debug_assertions) },
+            ChainFactsCheck::Always => quote! { true },
+        };
+
+        let facts = stream.facts();
+        let distinct = if facts.distinct().is_empty()
+            || facts
+                .distinct()
+                .iter()
+                .any(|datum_id| stream.sub_streams().contains_key(datum_id))
+        {
+            &[][..]
+        } else {
+            &facts.distinct()[..]
+        };
+        if facts.order().is_empty() && distinct.is_empty() {
+            return None;
+        }
+
+        let record_definition = &self.record_definitions[stream.record_type()];
+        let field = |datum_id| format_ident!("{}", record_definition[datum_id].name());
+
+        let order_keys = facts.order().iter().map(|directed| {
+            let field = field(**directed);
+            if directed.is_asc() {
+                quote! { record.#field().clone() }
+            } else {
+                quote! { std::cmp::Reverse(record.#field().clone()) }
+            }
+        });
+        let distinct_keys = distinct.iter().map(|datum_id| {
+            let field = field(*datum_id);
+            quote! { record.#field().clone() }
+        });
+        let distinct_check = if distinct.is_empty() {
+            quote! { None }
+        } else if facts.order().len() >= distinct.len()
+            && facts.order()[..distinct.len()]
+                .iter()
+                .all(|directed| distinct.contains(&**directed))
+        {
+            quote! { Adjacent }
+        } else {
+            quote! { Set }
+        };
+
+        let stream_name = stream.source().to_string();
+        let record = self.stream_definition_fragments(stream).record();
+        Some(quote! {
+            #enabled.then(|| {
+                datapet_support::chain::facts::record_check(
+                    datapet_support::chain::facts::FactsChecker::new(
+                        #stream_name,
+                        datapet_support::chain::facts::DistinctCheck::#distinct_check,
+                    ),
+                    |record: &#record| ((#(#order_keys,)*), (#(#distinct_keys,)*)),
+                )
+            })
+        })
+    }
+
+    /// The facts check of a stream received from another thread, unless the inline node producing
+    /// it already checks it.
+    fn receiver_facts_check(&self, stream: &NodeStream) -> Option<TokenStream> {
+        if self.checked_sources.contains(stream.source()) {
+            None
+        } else {
+            self.facts_check(stream)
+        }
+    }
+
     pub fn implement_inline_node(
         &mut self,
         node: &dyn DynNode,
@@ -608,6 +1616,7 @@ impl<'a> Chain<'a> {
         let name = node.name();
 
         let thread = self.get_thread_by_source(input, name, Some(output));
+        self.threads[thread.thread_id].nodes.push(name.clone());
 
         let record = self.stream_definition_fragments(output).record();
 
@@ -617,10 +1626,78 @@ impl<'a> Chain<'a> {
 
         let input = thread.format_input(input.source(), self.customizer, false);
 
+        let config = (!self.config_params.is_empty()).then(|| {
+            quote! {
+                #[allow(unused_variables)]
+                let config = thread_control.config.clone();
+            }
+        });
+
+        let inline_body = if let Some(check) = self.facts_check(output) {
+            self.checked_sources.insert(output.source().clone());
+            quote! {
+                let output = { #inline_body };
+                datapet_support::chain::facts::FactsCheckIterator::new(output, #check)
+            }
+        } else {
+            inline_body.clone()
+        };
+
+        let body = if self.customizer.error_context {
+            let name = name.to_string();
+            let thread_id = thread.thread_id;
+            quote! {
+                let output = { #inline_body };
+                datapet_support::chain::context::ContextIterator::new(output, #name, #thread_id)
+            }
+        } else {
+            inline_body
+        };
+
+        let body = if self.customizer.stream_metrics {
+            let name = name.to_string();
+            quote! {
+                let output = { #body };
+                datapet_support::chain::metrics::MeteredIterator::new(
+                    output,
+                    chain_configuration.metrics.stream_metrics(#name),
+                )
+            }
+        } else {
+            body
+        };
+
+        let body = if self.customizer.memory_groups {
+            let name = name.to_string();
+            quote! {
+                let output = { #body };
+                crate::dtpt_monitor::GroupIterator::new(output, #name)
+            }
+        } else {
+            body
+        };
+
+        let body = if self.customizer.tracing {
+            let name = name.to_string();
+            let thread_id = thread.thread_id;
+            quote! {
+                let output = { #body };
+                datapet_support::chain::tracing::TracedIterator::new(
+                    output,
+                    datapet_support::tracing::info_span!(#name, thread_id = #thread_id),
+                )
+            }
+        } else {
+            body
+        };
+
         let fn_def = quote! {
               pub fn #fn_name(#[allow(unused_mut)] mut thread_control: #thread_module::ThreadControl) -> impl FallibleIterator<Item = #record, Error = #error_type> {
+                  #[allow(unused_variables)]
+                  let chain_configuration = thread_control.chain_configuration.clone();
+                  #config
                   #input
-                  #inline_body
+                  #body
               }
         };
 
@@ -647,14 +1724,52 @@ impl<'a> Chain<'a> {
         thread_body: &TokenStream,
     ) {
         let name = node.name();
+        self.threads[thread_id].nodes.push(name.clone());
 
         let fn_name = format_ident!("{}", **name.last().expect("local name"));
         let thread_module = format_ident!("thread_{}", thread_id);
         let error_type = self.customizer.error_type.to_name();
 
+        let body = if self.customizer.error_context {
+            let name = name.to_string();
+            quote! {
+                let thread_main = { #thread_body };
+                move || {
+                    thread_main().map_err(|err| {
+                        datapet_support::chain::context::ErrorContext::with_context(
+                            err, #name, #thread_id, None,
+                        )
+                    })
+                }
+            }
+        } else {
+            thread_body.clone()
+        };
+
+        let body = if self.customizer.memory_groups {
+            let name = name.to_string();
+            quote! {
+                let thread_main = { #body };
+                move || crate::dtpt_monitor::run_in_group(#name, thread_main)
+            }
+        } else {
+            body
+        };
+
+        let body = if self.customizer.tracing {
+            let name = name.to_string();
+            quote! {
+                let thread_main = { #body };
+                let span = datapet_support::tracing::info_span!(#name, thread_id = #thread_id);
+                move || datapet_support::chain::tracing::run_traced(span, thread_main)
+            }
+        } else {
+            body
+        };
+
         let fn_def = quote! {
             pub fn #fn_name(#[allow(unused_mut)] mut thread_control: #thread_module::ThreadControl) -> impl FnOnce() -> Result<(), #error_type> {
-                #thread_body
+                #body
             }
         };
 
@@ -758,6 +1873,7 @@ pub const DEFAULT_CHAIN_ROOT_MODULE_NAME: [&str; 2] = ["crate", "chain"];
 pub const DEFAULT_CHAIN_STREAMS_MODULE_NAME: &str = "streams";
 pub const DEFAULT_CHAIN_ERROR_TYPE: [&str; 2] = ["datapet_support", "DatapetError"];
 pub const DEFAULT_CHAIN_MAIN_NAME: &str = "main";
+pub const DEFAULT_CHAIN_CHANNEL_CAPACITY: usize = 42;
 
 pub struct ChainCustomizer {
     pub streams_module_name: FullyQualifiedName,
@@ -766,6 +1882,41 @@ pub struct ChainCustomizer {
     pub error_type: FullyQualifiedName,
     pub main_name: String,
     pub main_attrs: Vec<String>,
+    /// The default capacity of the channels between threads, in batches if records are batched.
+    pub channel_capacity: usize,
+    /// Sends the records through the channels between threads in batches of that size, instead
+    /// of one at a time.
+    pub pipe_batch_size: Option<usize>,
+    /// Wraps the errors of the filters with their name, thread and record index, and converts the
+    /// failure of a thread, including a panic, into an error, which requires the error type to
+    /// implement `datapet_support::chain::context::ErrorContext`, as `DatapetError` does. On by
+    /// default, it is meant to be turned off only for custom error types which cannot carry the
+    /// context, in which case the panic of a thread is returned as a custom error naming it.
+    pub error_context: bool,
+    pub runtime: ChainRuntime,
+    /// Interrupts the chain on SIGINT or SIGTERM, like a thread failure does, and makes it
+    /// return `datapet_support::chain::cancellation::Interrupted` once its threads have stopped.
+    /// This requires the `signal-hook` feature of `datapet_support` and the error type to
+    /// implement `From<Interrupted>`.
+    pub handle_signals: bool,
+    /// Records the traffic of the channels and the output of the inline nodes into the
+    /// `metrics` of the chain configuration, by node name. Build scripts usually enable it along
+    /// with the `dtpt_monitor` feature, which writes the metrics to `dtpt_monitor_streams.csv`.
+    pub stream_metrics: bool,
+    /// Runs the thread mains and the inline nodes in `tracing` spans named after their node and
+    /// thread, with events for their start, end of stream, record count and errors. This requires
+    /// the `tracing` feature of `datapet_support` and the error type to implement `Display`.
+    pub tracing: bool,
+    /// Runs the thread mains and the inline nodes in their own `tracking_allocator` allocation
+    /// group, registered into the `dtpt_monitor` module which `tracking_allocator_static!`
+    /// defines at the crate root. Build scripts usually enable it along with the `dtpt_monitor`
+    /// feature, which then writes the memory held by each node to `dtpt_monitor_groups.csv`, one
+    /// row per node and report.
+    pub memory_groups: bool,
+    /// Checks at runtime that the streams respect their order and distinct facts, failing the
+    /// chain with `datapet_support::chain::facts::FactViolation` otherwise, which requires the
+    /// error type to implement `From<FactViolation>`.
+    pub facts_check: ChainFactsCheck,
 }
 
 impl ChainCustomizer {
@@ -799,6 +1950,15 @@ impl Default for ChainCustomizer {
             error_type: FullyQualifiedName::new_n(DEFAULT_CHAIN_ERROR_TYPE.iter()),
             main_name: DEFAULT_CHAIN_MAIN_NAME.to_string(),
             main_attrs: Vec::default(),
+            channel_capacity: DEFAULT_CHAIN_CHANNEL_CAPACITY,
+            pipe_batch_size: None,
+            error_context: true,
+            runtime: ChainRuntime::Threads,
+            handle_signals: false,
+            stream_metrics: false,
+            tracing: false,
+            memory_groups: false,
+            facts_check: ChainFactsCheck::Never,
         }
     }
 }
@@ -909,3 +2069,147 @@ macro_rules! trace_filter {
         $trace.sub($crate::trace_element!($name)).to_owned()
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use codegen::Scope;
+    use truc::record::type_resolver::{StaticTypeResolver, TypeResolver};
+
+    use crate::{
+        filter::{
+            fork::extract_fields::extract_fields,
+            function::{
+                produce::{function_produce, FunctionProduce},
+                terminate::function_terminate,
+                update::function_update,
+            },
+        },
+        prelude::*,
+    };
+
+    fn type_resolver() -> StaticTypeResolver {
+        let mut resolver = StaticTypeResolver::new();
+        resolver.add_std_types();
+        resolver
+    }
+
+    fn produce<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: &FullyQualifiedName,
+    ) -> FunctionProduce {
+        let params = graph
+            .params()
+            .from_ron_str(
+                r##"(
+                    fields: [("num", "u8")],
+                    body: r#"{ output.send(None)?; Ok(()) }"#,
+                )"##,
+            )
+            .unwrap();
+        function_produce(graph, name.sub("produce"), [], params, Trace::root()).unwrap()
+    }
+
+    #[test]
+    fn should_plan_linear_chain() {
+        let type_resolver = type_resolver();
+        let mut graph = GraphBuilder::new(&type_resolver, ChainCustomizer::default());
+        let name = FullyQualifiedName::new("main");
+
+        let produce = produce(&mut graph, &name);
+        let params = graph.params().from_ron_str(r#"(body: "input")"#).unwrap();
+        let update = function_update(
+            &mut graph,
+            name.sub("update"),
+            produce.outputs().clone(),
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+        let params = graph.params().from_ron_str(r#"(body: "Ok(())")"#).unwrap();
+        let terminate = function_terminate(
+            &mut graph,
+            name.sub("terminate"),
+            update.outputs().clone(),
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+
+        let graph = graph.build(vec![
+            Box::new(produce),
+            Box::new(update),
+            Box::new(terminate),
+        ]);
+        let plan = graph.gen_chain(&mut Scope::new());
+
+        assert_eq!(
+            "thread 0 main::produce (Regular): main main::produce, nodes [main::produce], \
+             input pipes [], output pipes [0]\n\
+             thread 1 main::update (Regular): main main::terminate, \
+             nodes [main::update, main::terminate], input pipes [0], output pipes []\n\
+             pipe 0: main::produce\n",
+            plan.to_string()
+        );
+    }
+
+    #[test]
+    fn should_plan_fork_join_chain() {
+        let type_resolver = type_resolver();
+        let mut graph = GraphBuilder::new(&type_resolver, ChainCustomizer::default());
+        let name = FullyQualifiedName::new("main");
+
+        let produce = produce(&mut graph, &name);
+        let params = graph.params().from_ron_str(r#"(fields: ["num"])"#).unwrap();
+        let extract = extract_fields(
+            &mut graph,
+            name.sub("extract"),
+            produce.outputs().clone(),
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+        let params = graph.params().from_ron_str(r#"(body: "input")"#).unwrap();
+        let update = function_update(
+            &mut graph,
+            name.sub("update"),
+            [extract.outputs()[0].clone()],
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+        let params = graph.params().from_ron_str(r#"(body: "Ok(())")"#).unwrap();
+        let terminate = function_terminate(
+            &mut graph,
+            name.sub("terminate"),
+            [update.outputs()[0].clone(), extract.outputs()[1].clone()],
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+
+        let graph = graph.build(vec![
+            Box::new(produce),
+            Box::new(extract),
+            Box::new(update),
+            Box::new(terminate),
+        ]);
+        let plan = graph.gen_chain(&mut Scope::new());
+
+        // The output of the inline update is piped to the join by its own thread
+        assert_eq!(
+            "thread 0 main::produce (Regular): main main::produce, nodes [main::produce], \
+             input pipes [], output pipes [0]\n\
+             thread 1 main::extract (Regular): main main::extract, nodes [main::extract], \
+             input pipes [0], output pipes [1, 2]\n\
+             thread 2 main::update (Regular): main thread_2::datapet_pipe, \
+             nodes [main::update], input pipes [1], output pipes [3]\n\
+             thread 3 main::terminate (Regular): main main::terminate, \
+             nodes [main::terminate], input pipes [3, 2], output pipes []\n\
+             pipe 0: main::produce\n\
+             pipe 1: main::extract\n\
+             pipe 2: main::extract::extracted\n\
+             pipe 3: main::update\n",
+            plan.to_string()
+        );
+    }
+}
@@ -1,6 +1,16 @@
-use crate::prelude::*;
+use datapet_support::data::buffer::BufferCompression;
+use serde::Deserialize;
 use truc::record::type_resolver::TypeResolver;
 
+use crate::{prelude::*, support::buffer::buffer_options};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct AccumulateParams {
+    /// The compression of the accumulated records once spilled.
+    compression: Option<BufferCompression>,
+}
+
 #[derive(Getters)]
 pub struct Accumulate {
     name: FullyQualifiedName,
@@ -8,6 +18,7 @@ pub struct Accumulate {
     inputs: [NodeStream; 1],
     #[getset(get = "pub")]
     outputs: [NodeStream; 1],
+    compression: BufferCompression,
 }
 
 impl Accumulate {
@@ -15,7 +26,7 @@ impl Accumulate {
         graph: &mut GraphBuilder<R>,
         name: FullyQualifiedName,
         inputs: [NodeStream; 1],
-        _params: (),
+        params: AccumulateParams,
         _trace: Trace,
     ) -> ChainResult<Self> {
         let mut streams = StreamsBuilder::new(&name, &inputs);
@@ -30,6 +41,7 @@ impl Accumulate {
             name,
             inputs,
             outputs,
+            compression: params.compression.unwrap_or_default(),
         })
     }
 }
@@ -48,8 +60,9 @@ impl DynNode for Accumulate {
     }
 
     fn gen_chain(&self, _graph: &Graph, chain: &mut Chain) {
+        let buffer_options = buffer_options(&self.name, self.compression);
         let inline_body = quote! {
-            datapet_support::iterator::accumulate::Accumulate::new(input)
+            datapet_support::iterator::accumulate::Accumulate::with_options(input, #buffer_options)
         };
 
         chain.implement_inline_node(
@@ -69,7 +82,7 @@ pub fn accumulate<R: TypeResolver + Copy>(
     graph: &mut GraphBuilder<R>,
     name: FullyQualifiedName,
     inputs: [NodeStream; 1],
-    params: (),
+    params: AccumulateParams,
     trace: Trace,
 ) -> ChainResult<Accumulate> {
     Accumulate::new(graph, name, inputs, params, trace)
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+
+use proc_macro2::TokenStream;
+use truc::record::{definition::DatumId, type_resolver::TypeResolver};
+
+use crate::prelude::*;
+
+pub mod write;
+
+/// Arrow mapping of a scalar field type.
+#[derive(PartialEq, Eq, Debug)]
+pub struct ArrowScalar {
+    /// The `arrow::datatypes::DataType` variant.
+    pub data_type: &'static str,
+    /// The `arrow::array` builder type.
+    pub builder: &'static str,
+    pub nullable: bool,
+    /// String values are appended by reference, other values are copied.
+    pub string: bool,
+}
+
+/// Maps a scalar field type to an Arrow type, `None` if the type is not supported.
+pub fn arrow_scalar(type_name: &str) -> Option<ArrowScalar> {
+    let type_name = type_name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let (type_name, nullable) = match type_name
+        .strip_prefix("Option<")
+        .and_then(|t| t.strip_suffix('>'))
+    {
+        Some(inner) => (inner, true),
+        None => (type_name.as_str(), false),
+    };
+    let (data_type, builder, string) = match type_name {
+        "bool" => ("Boolean", "BooleanBuilder", false),
+        "i8" => ("Int8", "Int8Builder", false),
+        "i16" => ("Int16", "Int16Builder", false),
+        "i32" => ("Int32", "Int32Builder", false),
+        "i64" => ("Int64", "Int64Builder", false),
+        "u8" => ("UInt8", "UInt8Builder", false),
+        "u16" => ("UInt16", "UInt16Builder", false),
+        "u32" => ("UInt32", "UInt32Builder", false),
+        "u64" => ("UInt64", "UInt64Builder", false),
+        "f32" => ("Float32", "Float32Builder", false),
+        "f64" => ("Float64", "Float64Builder", false),
+        "String" | "Box<str>" => ("Utf8", "StringBuilder", true),
+        _ => return None,
+    };
+    Some(ArrowScalar {
+        data_type,
+        builder,
+        nullable,
+        string,
+    })
+}
+
+pub enum ArrowColumnType {
+    Scalar(ArrowScalar),
+    /// A sub stream, mapped to a list of structs.
+    List(Vec<ArrowColumn>),
+}
+
+pub struct ArrowColumn {
+    pub name: ValidFieldName,
+    pub column_type: ArrowColumnType,
+}
+
+/// Builds the Arrow columns of a stream record, recursing into sub streams.
+///
+/// Fails on the first field which cannot be mapped.
+pub fn arrow_columns<R: TypeResolver + Copy, TRACE>(
+    graph: &GraphBuilder<R>,
+    record_type: &StreamRecordType,
+    sub_streams: &BTreeMap<DatumId, NodeSubStream>,
+    trace: &TRACE,
+) -> ChainResult<Vec<ArrowColumn>>
+where
+    TRACE: Fn() -> Trace<'static>,
+{
+    let def = graph
+        .get_stream(record_type)
+        .unwrap_or_else(|| panic!(r#"stream "{}""#, record_type))
+        .borrow();
+    def.get_current_data()
+        .map(|d| {
+            let datum = def.get_datum_definition(d).expect("datum");
+            let name =
+                ValidFieldName::try_from(datum.name()).map_err(|_| ChainError::InvalidFieldName {
+                    name: datum.name().to_owned(),
+                    trace: trace(),
+                })?;
+            let column_type = if let Some(sub_stream) = sub_streams.get(&d) {
+                ArrowColumnType::List(arrow_columns(
+                    graph,
+                    sub_stream.record_type(),
+                    sub_stream.sub_streams(),
+                    trace,
+                )?)
+            } else {
+                ArrowColumnType::Scalar(arrow_scalar(datum.type_name()).ok_or_else(|| {
+                    ChainError::InvalidFieldType {
+                        type_name: datum.type_name().to_owned(),
+                        trace: trace(),
+                    }
+                })?)
+            };
+            Ok(ArrowColumn { name, column_type })
+        })
+        .collect()
+}
+
+impl ArrowColumn {
+    /// Generates the `arrow::datatypes::Field` of the column.
+    pub fn gen_field(&self) -> TokenStream {
+        let name = self.name.name();
+        match &self.column_type {
+            ArrowColumnType::Scalar(scalar) => {
+                let data_type = format_ident!("{}", scalar.data_type);
+                let nullable = scalar.nullable;
+                quote! {
+                    arrow::datatypes::Field::new(#name, arrow::datatypes::DataType::#data_type, #nullable)
+                }
+            }
+            ArrowColumnType::List(columns) => {
+                let data_type = gen_struct_data_type(columns);
+                // The item field is nullable, as built by arrow::array::ListBuilder.
+                quote! {
+                    arrow::datatypes::Field::new(
+                        #name,
+                        arrow::datatypes::DataType::List(Box::new(
+                            arrow::datatypes::Field::new("item", #data_type, true)
+                        )),
+                        false,
+                    )
+                }
+            }
+        }
+    }
+
+    /// Generates the type of the builder of the column.
+    pub fn gen_builder_type(&self) -> TokenStream {
+        match &self.column_type {
+            ArrowColumnType::Scalar(scalar) => {
+                let builder = format_ident!("{}", scalar.builder);
+                quote! { arrow::array::#builder }
+            }
+            ArrowColumnType::List(_) => {
+                quote! { arrow::array::ListBuilder<arrow::array::StructBuilder> }
+            }
+        }
+    }
+
+    /// Generates the expression creating the builder of the column.
+    pub fn gen_builder(&self) -> TokenStream {
+        match &self.column_type {
+            ArrowColumnType::Scalar(scalar) => {
+                let builder = format_ident!("{}", scalar.builder);
+                quote! { arrow::array::#builder::new() }
+            }
+            ArrowColumnType::List(columns) => {
+                let fields = columns.iter().map(ArrowColumn::gen_field);
+                let builders = columns.iter().map(ArrowColumn::gen_builder);
+                quote! {
+                    arrow::array::ListBuilder::new(arrow::array::StructBuilder::new(
+                        vec![#(#fields),*],
+                        vec![#(Box::new(#builders) as Box<dyn arrow::array::ArrayBuilder>),*],
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Generates the statement appending `value` (a reference to the field value) to `builder`
+    /// (a mutable reference to the builder of the column).
+    pub fn gen_append(&self, builder: &TokenStream, value: &TokenStream) -> TokenStream {
+        match &self.column_type {
+            ArrowColumnType::Scalar(scalar) => match (scalar.nullable, scalar.string) {
+                (false, false) => quote! { #builder.append_value(*#value); },
+                (false, true) => quote! { #builder.append_value(#value); },
+                (true, false) => quote! { #builder.append_option(*#value); },
+                (true, true) => quote! { #builder.append_option(#value.as_deref()); },
+            },
+            ArrowColumnType::List(columns) => {
+                let appends = columns.iter().enumerate().map(|(index, column)| {
+                    let builder_type = column.gen_builder_type();
+                    let field = column.name.ident();
+                    column.gen_append(
+                        &quote! {
+                            struct_builder
+                                .field_builder::<#builder_type>(#index)
+                                .expect("field builder")
+                        },
+                        &quote! { sub_record.#field() },
+                    )
+                });
+                quote! {
+                    {
+                        let list_builder = #builder;
+                        for sub_record in #value.iter() {
+                            let struct_builder = list_builder.values();
+                            #(#appends)*
+                            struct_builder.append(true);
+                        }
+                        list_builder.append(true);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn gen_struct_data_type(columns: &[ArrowColumn]) -> TokenStream {
+    let fields = columns.iter().map(ArrowColumn::gen_field);
+    quote! { arrow::datatypes::DataType::Struct(vec![#(#fields),*]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arrow_scalar, ArrowColumn, ArrowColumnType, ArrowScalar};
+    use crate::support::valid::ValidFieldName;
+
+    #[test]
+    fn test_arrow_scalar_mapping() {
+        assert_eq!(
+            arrow_scalar("u32"),
+            Some(ArrowScalar {
+                data_type: "UInt32",
+                builder: "UInt32Builder",
+                nullable: false,
+                string: false,
+            })
+        );
+        assert_eq!(
+            arrow_scalar("Option<Box<str>>"),
+            Some(ArrowScalar {
+                data_type: "Utf8",
+                builder: "StringBuilder",
+                nullable: true,
+                string: true,
+            })
+        );
+        assert_eq!(arrow_scalar("usize"), None);
+        assert_eq!(arrow_scalar("Vec<u8>"), None);
+    }
+
+    #[test]
+    fn test_arrow_list_field() {
+        let column = ArrowColumn {
+            name: ValidFieldName::try_from("group").unwrap(),
+            column_type: ArrowColumnType::List(vec![ArrowColumn {
+                name: ValidFieldName::try_from("num").unwrap(),
+                column_type: ArrowColumnType::Scalar(arrow_scalar("u8").unwrap()),
+            }]),
+        };
+        assert_eq!(
+            column.gen_field().to_string(),
+            concat!(
+                "arrow :: datatypes :: Field :: new (\"group\" , ",
+                "arrow :: datatypes :: DataType :: List (Box :: new (",
+                "arrow :: datatypes :: Field :: new (\"item\" , ",
+                "arrow :: datatypes :: DataType :: Struct (vec ! [",
+                "arrow :: datatypes :: Field :: new (\"num\" , arrow :: datatypes :: DataType :: UInt8 , false)",
+                "]) , true))) , false ,)"
+            )
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use datapet_support::iterator::io::compression::Compression;
+use serde::Deserialize;
+use truc::record::type_resolver::TypeResolver;
+
+use super::{arrow_columns, ArrowColumn};
+use crate::{prelude::*, support::compression::file_compression, trace_filter};
+
+const WRITE_ARROW_IPC_TRACE_NAME: &str = "write_arrow_ipc";
+const WRITE_PARQUET_TRACE_NAME: &str = "write_parquet";
+
+const DEFAULT_BATCH_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArrowFormat {
+    Ipc,
+    Parquet,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WriteArrowParams<'a> {
+    path: &'a str,
+    /// The number of records per record batch, which is also the row group size for Parquet.
+    batch_size: Option<usize>,
+    /// The compression of the whole file, detected from its extension if not specified.
+    compression: Option<Compression>,
+}
+
+#[derive(Getters)]
+pub struct WriteArrow {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; 1],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 0],
+    format: ArrowFormat,
+    path: String,
+    columns: Vec<ArrowColumn>,
+    batch_size: usize,
+    compression: Option<Compression>,
+}
+
+impl WriteArrow {
+    fn new<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; 1],
+        params: WriteArrowParams,
+        format: ArrowFormat,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        let trace_name = match format {
+            ArrowFormat::Ipc => WRITE_ARROW_IPC_TRACE_NAME,
+            ArrowFormat::Parquet => WRITE_PARQUET_TRACE_NAME,
+        };
+
+        let columns = arrow_columns(
+            graph,
+            inputs[0].record_type(),
+            inputs[0].sub_streams(),
+            &|| trace_filter!(trace, trace_name),
+        )?;
+
+        let batch_size = params.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+        if batch_size == 0 {
+            return Err(ChainError::Other {
+                msg: "batch_size must be greater than 0".to_owned(),
+                trace: trace_filter!(trace, trace_name),
+            });
+        }
+
+        Ok(Self {
+            name,
+            inputs,
+            outputs: [],
+            format,
+            path: params.path.to_owned(),
+            columns,
+            batch_size,
+            compression: params.compression,
+        })
+    }
+}
+
+impl DynNode for WriteArrow {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        let thread = chain.get_thread_by_source(&self.inputs[0], &self.name, self.outputs.none());
+
+        let input = thread.format_input(self.inputs[0].source(), graph.chain_customizer(), true);
+
+        let error_type = graph.chain_customizer().error_type.to_name();
+
+        let name = self.name.to_string();
+        let path = &self.path;
+        let batch_size = self.batch_size;
+        let compression = file_compression(self.compression);
+
+        let fields = self.columns.iter().map(ArrowColumn::gen_field);
+        let builder_names = (0..self.columns.len())
+            .map(|i| format_ident!("builder_{}", i))
+            .collect::<Vec<_>>();
+        let builders = self.columns.iter().map(ArrowColumn::gen_builder);
+        let appends = self
+            .columns
+            .iter()
+            .zip(builder_names.iter())
+            .map(|(column, builder_name)| {
+                let field = column.name.ident();
+                column.gen_append(
+                    &quote! { (&mut #builder_name) },
+                    &quote! { record.#field() },
+                )
+            });
+
+        let (new_writer, close_writer) = match self.format {
+            ArrowFormat::Ipc => (
+                quote! {
+                    let mut writer = arrow::ipc::writer::FileWriter::try_new(file, &schema)
+                        .map_err(arrow_error)?;
+                },
+                quote! {
+                    writer.finish().map_err(arrow_error)?;
+                    let file = writer.into_inner().map_err(arrow_error)?;
+                },
+            ),
+            ArrowFormat::Parquet => (
+                quote! {
+                    let parquet_error =
+                        |err: parquet::errors::ParquetError| #error_type::custom(err.to_string());
+                    let properties = parquet::file::properties::WriterProperties::builder()
+                        .set_max_row_group_size(#batch_size)
+                        .build();
+                    let mut writer = parquet::arrow::ArrowWriter::try_new(file, schema.clone(), Some(properties))
+                        .map_err(parquet_error)?;
+                },
+                quote! {
+                    // Closes the writer before giving the file back
+                    let file = writer.into_inner().map_err(parquet_error)?;
+                },
+            ),
+        };
+        let write_error = match self.format {
+            ArrowFormat::Ipc => quote! { arrow_error },
+            ArrowFormat::Parquet => quote! { parquet_error },
+        };
+
+        let thread_body = quote! {
+            #input
+
+            let path = thread_control.chain_configuration.path(#name, #path);
+            move || {
+                use std::sync::Arc;
+                use fallible_iterator::FallibleIterator;
+
+                let arrow_error =
+                    |err: arrow::error::ArrowError| #error_type::custom(err.to_string());
+
+                let schema = Arc::new(arrow::datatypes::Schema::new(vec![#(#fields),*]));
+
+                let file = datapet_support::iterator::io::compression::create(path, #compression)
+                    .map_err(|err| #error_type::custom(err.to_string()))?;
+                #new_writer
+
+                #(let mut #builder_names = #builders;)*
+
+                let mut done = false;
+                while !done {
+                    let mut count = 0;
+                    while count < #batch_size {
+                        if let Some(record) = input.next()? {
+                            #(#appends)*
+                            count += 1;
+                        } else {
+                            done = true;
+                            break;
+                        }
+                    }
+                    if count > 0 {
+                        let batch = arrow::record_batch::RecordBatch::try_new(
+                            schema.clone(),
+                            vec![#(Arc::new(#builder_names.finish()) as arrow::array::ArrayRef),*],
+                        )
+                        .map_err(arrow_error)?;
+                        writer.write(&batch).map_err(#write_error)?;
+                    }
+                }
+
+                #close_writer
+                file.finish()
+                    .map_err(|err| #error_type::custom(err.to_string()))?;
+
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread.thread_id, &thread_body);
+
+        chain.set_thread_main(thread.thread_id, self.name.clone());
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn write_arrow_ipc<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 1],
+    params: WriteArrowParams,
+    trace: Trace,
+) -> ChainResult<WriteArrow> {
+    WriteArrow::new(graph, name, inputs, params, ArrowFormat::Ipc, trace)
+}
+
+pub fn write_parquet<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 1],
+    params: WriteArrowParams,
+    trace: Trace,
+) -> ChainResult<WriteArrow> {
+    WriteArrow::new(graph, name, inputs, params, ArrowFormat::Parquet, trace)
+}
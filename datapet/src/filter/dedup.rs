@@ -1,7 +1,15 @@
 use serde::Deserialize;
 use truc::record::type_resolver::TypeResolver;
 
-use crate::{prelude::*, support::eq::fields_eq, trace_filter};
+use crate::{
+    filter::sort::{sort_if_unordered, Sort},
+    graph::builder::check_undirected_order_starts_with,
+    prelude::*,
+    support::eq::fields_eq,
+    trace_filter,
+};
+
+const DEDUP_TRACE_NAME: &str = "dedup";
 
 #[derive(Getters)]
 pub struct Dedup {
@@ -10,6 +18,8 @@ pub struct Dedup {
     inputs: [NodeStream; 1],
     #[getset(get = "pub")]
     outputs: [NodeStream; 1],
+    /// The sort inserted before the dedup in auto-order mode.
+    auto_sort: Option<Sort>,
 }
 
 impl Dedup {
@@ -18,8 +28,70 @@ impl Dedup {
         name: FullyQualifiedName,
         inputs: [NodeStream; 1],
         _params: (),
-        _trace: Trace,
+        trace: Trace,
     ) -> ChainResult<Self> {
+        let [input] = inputs;
+        let (input, auto_sort) = if graph.auto_order() {
+            // Duplicated records are only adjacent when the input is ordered by all its fields
+            let fields = {
+                let def = graph
+                    .get_stream(input.record_type())
+                    .unwrap_or_else(|| panic!(r#"stream "{}""#, input.record_type()))
+                    .borrow();
+                def[input.variant_id()]
+                    .data()
+                    .map(|d| def[d].name().to_owned())
+                    .collect::<Vec<String>>()
+            };
+            let directed_fields = fields
+                .iter()
+                .map(|field| Directed::Ascending(field.as_str()))
+                .collect::<Vec<_>>();
+            sort_if_unordered(
+                graph,
+                name.sub(format!("{}_auto_sort", name.to_name())),
+                input,
+                &directed_fields,
+                |graph, input| {
+                    let def = graph
+                        .get_stream(input.record_type())
+                        .unwrap_or_else(|| panic!(r#"stream "{}""#, input.record_type()))
+                        .borrow();
+                    let datum_ids = def[input.variant_id()].data().collect::<Vec<_>>();
+                    check_undirected_order_starts_with(
+                        &datum_ids,
+                        input.facts().order(),
+                        &*def,
+                        "main stream",
+                        || trace_filter!(trace, DEDUP_TRACE_NAME),
+                    )
+                    .map_err(|err| {
+                        // Sub-streams cannot be sorted on, and leaving them out of the sort would
+                        // not make the duplicated records adjacent
+                        match datum_ids
+                            .iter()
+                            .find(|d| input.sub_streams().contains_key(*d))
+                        {
+                            Some(d) if matches!(err, ChainError::ExpectedMinimalOrder { .. }) => {
+                                ChainError::Other {
+                                    msg: format!(
+                                        "field `{}` is a sub-stream, the input cannot be sorted \
+                                         to be deduplicated",
+                                        def[*d].name()
+                                    ),
+                                    trace: trace_filter!(trace, DEDUP_TRACE_NAME),
+                                }
+                            }
+                            _ => err,
+                        }
+                    })
+                },
+                trace.clone(),
+            )?
+        } else {
+            (input, None)
+        };
+        let inputs = [input];
         let mut streams = StreamsBuilder::new(&name, &inputs);
         streams
             .output_from_input(0, true, graph)
@@ -32,6 +104,7 @@ impl Dedup {
             name,
             inputs,
             outputs,
+            auto_sort,
         })
     }
 }
@@ -50,6 +123,10 @@ impl DynNode for Dedup {
     }
 
     fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        if let Some(auto_sort) = &self.auto_sort {
+            auto_sort.gen_chain(graph, chain);
+        }
+
         let record = chain
             .stream_definition_fragments(self.inputs.single())
             .record();
@@ -71,7 +148,12 @@ impl DynNode for Dedup {
     }
 
     fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
-        Box::new(Some(self as &dyn DynNode).into_iter())
+        Box::new(
+            self.auto_sort
+                .iter()
+                .map(|sort| sort as &dyn DynNode)
+                .chain(Some(self as &dyn DynNode)),
+        )
     }
 }
 
@@ -226,3 +308,64 @@ pub fn sub_dedup<R: TypeResolver + Copy>(
 ) -> ChainResult<SubDedup> {
     SubDedup::new(graph, name, inputs, params, trace)
 }
+
+#[cfg(test)]
+mod tests {
+    use truc::record::type_resolver::StaticTypeResolver;
+
+    use super::dedup;
+    use crate::{
+        filter::{function::produce::function_produce, group::group},
+        prelude::*,
+    };
+
+    #[test]
+    fn should_not_auto_sort_on_sub_stream() {
+        let type_resolver = {
+            let mut resolver = StaticTypeResolver::new();
+            resolver.add_std_types();
+            resolver
+        };
+        let mut graph = GraphBuilder::new(&type_resolver, ChainCustomizer::default());
+        graph.set_auto_order(true);
+        let name = FullyQualifiedName::new("main");
+
+        let params = graph
+            .params()
+            .from_ron_str(
+                r##"(
+                    fields: [("num", "u8"), ("lsb2", "u8")],
+                    body: r#"{ output.send(None)?; Ok(()) }"#,
+                )"##,
+            )
+            .unwrap();
+        let produce =
+            function_produce(&mut graph, name.sub("produce"), [], params, Trace::root()).unwrap();
+        let params = graph
+            .params()
+            .from_ron_str(r#"(fields: ["num"], group_field: "nums")"#)
+            .unwrap();
+        let group = group(
+            &mut graph,
+            name.sub("group"),
+            produce.outputs().clone(),
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+
+        // The groups are ordered by their key only
+        let result = dedup(
+            &mut graph,
+            name.sub("dedup"),
+            group.outputs().clone(),
+            (),
+            Trace::root(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(ChainError::Other { msg, .. }) if msg.starts_with("field `nums` is a sub-stream")
+        ));
+    }
+}
@@ -0,0 +1,472 @@
+use datapet_support::iterator::io::compression::Compression;
+use proc_macro2::TokenStream;
+use serde::Deserialize;
+use truc::record::type_resolver::TypeResolver;
+
+use crate::{prelude::*, support::compression::file_compression, trace_filter};
+
+const WRITE_DOT_TRACE_NAME: &str = "write_dot";
+
+const DEFAULT_GRAPH_NAME: &str = "dtpt";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WriteDotParams<'a> {
+    name: Option<&'a str>,
+    /// Output file, the standard output if not specified.
+    path: Option<&'a str>,
+    /// The compression of the output file, detected from its extension if not specified.
+    compression: Option<Compression>,
+    /// The declarations of each input.
+    #[serde(borrow)]
+    inputs: Vec<Vec<DotElementParam<'a>>>,
+}
+
+/// Fields may be paths into a sub stream, e.g. `"refs.anchor"`, in which case one element is
+/// written per sub record.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub enum DotElementParam<'a> {
+    Nodes {
+        id_field: &'a str,
+        id_prefix: Option<&'a str>,
+        label_field: Option<&'a str>,
+    },
+    Edges {
+        from_field: &'a str,
+        from_prefix: Option<&'a str>,
+        to_field: &'a str,
+        to_prefix: Option<&'a str>,
+    },
+}
+
+#[derive(Debug)]
+enum DotField {
+    Field(ValidFieldName),
+    SubField {
+        sub_stream: ValidFieldName,
+        field: ValidFieldName,
+    },
+}
+
+impl DotField {
+    fn sub_stream(&self) -> Option<&ValidFieldName> {
+        match self {
+            DotField::Field(_) => None,
+            DotField::SubField { sub_stream, .. } => Some(sub_stream),
+        }
+    }
+
+    fn gen_access(&self) -> TokenStream {
+        match self {
+            DotField::Field(field) => {
+                let field = field.ident();
+                quote! { record.#field() }
+            }
+            DotField::SubField { field, .. } => {
+                let field = field.ident();
+                quote! { sub_record.#field() }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum DotElement {
+    Nodes {
+        id: DotField,
+        id_prefix: String,
+        label: Option<DotField>,
+    },
+    Edges {
+        from: DotField,
+        from_prefix: String,
+        to: DotField,
+        to_prefix: String,
+    },
+}
+
+impl DotElement {
+    fn sub_stream(&self) -> Option<&ValidFieldName> {
+        match self {
+            DotElement::Nodes { id, label, .. } => id
+                .sub_stream()
+                .or_else(|| label.as_ref().and_then(DotField::sub_stream)),
+            DotElement::Edges { from, to, .. } => from.sub_stream().or_else(|| to.sub_stream()),
+        }
+    }
+
+    fn gen_write(&self, io_error: &TokenStream) -> TokenStream {
+        let write = match self {
+            DotElement::Nodes {
+                id,
+                id_prefix,
+                label,
+            } => {
+                let id = id.gen_access();
+                let label = if let Some(label) = label {
+                    let label = label.gen_access();
+                    quote! { Some(#label as &dyn std::fmt::Display) }
+                } else {
+                    quote! { None }
+                };
+                quote! {
+                    writer.node(#id_prefix, #id, #label).map_err(#io_error)?;
+                }
+            }
+            DotElement::Edges {
+                from,
+                from_prefix,
+                to,
+                to_prefix,
+            } => {
+                let from = from.gen_access();
+                let to = to.gen_access();
+                quote! {
+                    writer.edge(#from_prefix, #from, #to_prefix, #to).map_err(#io_error)?;
+                }
+            }
+        };
+        if let Some(sub_stream) = self.sub_stream() {
+            let sub_stream = sub_stream.ident();
+            quote! {
+                for sub_record in record.#sub_stream().iter() {
+                    #write
+                }
+            }
+        } else {
+            write
+        }
+    }
+}
+
+#[derive(Getters)]
+pub struct WriteDot<const N: usize> {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; N],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 0],
+    graph_name: String,
+    path: Option<String>,
+    compression: Option<Compression>,
+    elements: Vec<Vec<DotElement>>,
+}
+
+impl<const N: usize> WriteDot<N> {
+    fn new<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; N],
+        params: WriteDotParams,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        if params.inputs.len() != N {
+            return Err(ChainError::Other {
+                msg: format!(
+                    "Expected {} input declarations but found {}",
+                    N,
+                    params.inputs.len()
+                ),
+                trace: trace_filter!(trace, WRITE_DOT_TRACE_NAME),
+            });
+        }
+
+        if params.compression.is_some() && params.path.is_none() {
+            return Err(ChainError::Other {
+                msg: "compression requires a path".to_owned(),
+                trace: trace_filter!(trace, WRITE_DOT_TRACE_NAME),
+            });
+        }
+
+        let elements = params
+            .inputs
+            .into_iter()
+            .zip(inputs.iter())
+            .map(|(elements, input)| {
+                elements
+                    .into_iter()
+                    .map(|element| Self::validate_element(graph, input, element, &trace))
+                    .collect::<ChainResult<Vec<_>>>()
+            })
+            .collect::<ChainResult<Vec<_>>>()?;
+
+        Ok(Self {
+            name,
+            inputs,
+            outputs: [],
+            graph_name: params.name.unwrap_or(DEFAULT_GRAPH_NAME).to_owned(),
+            path: params.path.map(ToOwned::to_owned),
+            compression: params.compression,
+            elements,
+        })
+    }
+
+    fn validate_element<R: TypeResolver + Copy>(
+        graph: &GraphBuilder<R>,
+        input: &NodeStream,
+        element: DotElementParam,
+        trace: &Trace,
+    ) -> ChainResult<DotElement> {
+        let element = match element {
+            DotElementParam::Nodes {
+                id_field,
+                id_prefix,
+                label_field,
+            } => DotElement::Nodes {
+                id: Self::validate_field(graph, input, id_field, trace)?,
+                id_prefix: id_prefix.unwrap_or_default().to_owned(),
+                label: label_field
+                    .map(|label_field| Self::validate_field(graph, input, label_field, trace))
+                    .transpose()?,
+            },
+            DotElementParam::Edges {
+                from_field,
+                from_prefix,
+                to_field,
+                to_prefix,
+            } => DotElement::Edges {
+                from: Self::validate_field(graph, input, from_field, trace)?,
+                from_prefix: from_prefix.unwrap_or_default().to_owned(),
+                to: Self::validate_field(graph, input, to_field, trace)?,
+                to_prefix: to_prefix.unwrap_or_default().to_owned(),
+            },
+        };
+        let sub_streams = match &element {
+            DotElement::Nodes { id, label, .. } => {
+                [Some(id), label.as_ref()].map(|field| field.and_then(DotField::sub_stream))
+            }
+            DotElement::Edges { from, to, .. } => [from.sub_stream(), to.sub_stream()],
+        };
+        if let [Some(a), Some(b)] = sub_streams {
+            if a != b {
+                return Err(ChainError::Other {
+                    msg: format!(
+                        "Fields of a dot element cannot iterate over different sub streams {} and {}",
+                        a.name(),
+                        b.name()
+                    ),
+                    trace: trace_filter!(trace, WRITE_DOT_TRACE_NAME),
+                });
+            }
+        }
+        Ok(element)
+    }
+
+    fn validate_field<R: TypeResolver + Copy>(
+        graph: &GraphBuilder<R>,
+        input: &NodeStream,
+        field: &str,
+        trace: &Trace,
+    ) -> ChainResult<DotField> {
+        let valid_name = |name: &str| {
+            ValidFieldName::try_from(name).map_err(|_| ChainError::InvalidFieldName {
+                name: name.to_owned(),
+                trace: trace_filter!(trace, WRITE_DOT_TRACE_NAME),
+            })
+        };
+        let field_not_found = |name: &str| ChainError::FieldNotFound {
+            field: name.to_owned(),
+            trace: trace_filter!(trace, WRITE_DOT_TRACE_NAME),
+        };
+        let def = graph
+            .get_stream(input.record_type())
+            .unwrap_or_else(|| panic!(r#"stream "{}""#, input.record_type()))
+            .borrow();
+        if let Some((sub_stream_name, field)) = field.split_once('.') {
+            let sub_stream = def
+                .get_current_datum_definition_by_name(sub_stream_name)
+                .and_then(|datum| input.sub_streams().get(&datum.id()))
+                .ok_or_else(|| field_not_found(sub_stream_name))?;
+            let sub_def = graph
+                .get_stream(sub_stream.record_type())
+                .unwrap_or_else(|| panic!(r#"stream "{}""#, sub_stream.record_type()))
+                .borrow();
+            if sub_def.get_current_datum_definition_by_name(field).is_none() {
+                return Err(field_not_found(field));
+            }
+            Ok(DotField::SubField {
+                sub_stream: valid_name(sub_stream_name)?,
+                field: valid_name(field)?,
+            })
+        } else {
+            if def.get_current_datum_definition_by_name(field).is_none() {
+                return Err(field_not_found(field));
+            }
+            Ok(DotField::Field(valid_name(field)?))
+        }
+    }
+}
+
+impl<const N: usize> DynNode for WriteDot<N> {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        let error_type = graph.chain_customizer().error_type.to_name();
+
+        let (thread_id, inputs) = if self.inputs.len() == 1 {
+            let thread =
+                chain.get_thread_by_source(&self.inputs[0], &self.name, self.outputs.none());
+
+            let input =
+                thread.format_input(self.inputs[0].source(), graph.chain_customizer(), false);
+
+            (
+                thread.thread_id,
+                vec![quote! {
+                    #input
+                    let input_0 = input;
+                }],
+            )
+        } else {
+            let thread_id = chain.pipe_inputs(&self.name, &self.inputs, &self.outputs);
+
+            let inputs = (0..self.inputs.len())
+                .map(|input_index| {
+                    let input = format_ident!("input_{}", input_index);
+                    let expect = format!("input {}", input_index);
+                    quote! {
+                        let #input = datapet_support::iterator::sync::mpsc::Receive::<_, #error_type, _>::new(
+                            thread_control.#input.take().expect(#expect),
+                        );
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            (thread_id, inputs)
+        };
+
+        let io_error = quote! { io_error };
+
+        let readers = self
+            .elements
+            .iter()
+            .enumerate()
+            .map(|(input_index, elements)| {
+                let input = format_ident!("input_{}", input_index);
+                let writes = elements
+                    .iter()
+                    .map(|element| element.gen_write(&io_error));
+                quote! {
+                    move || -> Result<(), #error_type> {
+                        let mut input = #input;
+                        while let Some(record) = input.next()? {
+                            let mut writer = writer.lock().expect("writer");
+                            #(#writes)*
+                        }
+                        Ok(())
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let read_all = if readers.len() == 1 {
+            let reader = &readers[0];
+            quote! {
+                let writer = &writer;
+                (#reader)()?;
+            }
+        } else {
+            // One thread per input: the inputs are merged as soon as records are available.
+            quote! {
+                std::thread::scope(|scope| -> Result<(), #error_type> {
+                    let writer = &writer;
+                    let handles = vec![#(scope.spawn(#readers)),*];
+                    for (index, handle) in handles.into_iter().enumerate() {
+                        handle.join().map_err(|payload| {
+                            #error_type::custom(format!(
+                                "dot input {} panicked: {}",
+                                index,
+                                datapet_support::chain::cancellation::panic_message(payload),
+                            ))
+                        })??;
+                    }
+                    Ok(())
+                })?;
+            }
+        };
+
+        let graph_name = &self.graph_name;
+        let path = self.path.as_ref().map(|path| {
+            let name = self.name.to_string();
+            quote! {
+                let path = thread_control.chain_configuration.path(#name, #path);
+            }
+        });
+        let (output, finish_output) = if self.path.is_some() {
+            let compression = file_compression(self.compression);
+            (
+                quote! {
+                    let output = datapet_support::iterator::io::compression::create(path, #compression)
+                        .map_err(io_error)?;
+                },
+                quote! {
+                    .finish()
+                    .map_err(io_error)?
+                },
+            )
+        } else {
+            (
+                quote! {
+                    let output = std::io::stdout();
+                },
+                quote! {},
+            )
+        };
+
+        let thread_body = quote! {
+            #(#inputs)*
+            #path
+
+            move || {
+                use fallible_iterator::FallibleIterator;
+
+                let io_error = |err: std::io::Error| #error_type::custom(err.to_string());
+
+                #output
+                let writer = std::sync::Mutex::new(
+                    datapet_support::iterator::io::dot::DotWriter::new(output, #graph_name)
+                        .map_err(io_error)?,
+                );
+
+                #read_all
+
+                writer
+                    .into_inner()
+                    .expect("writer")
+                    .finish()
+                    .map_err(io_error)?
+                    #finish_output;
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread_id, &thread_body);
+
+        chain.set_thread_main(thread_id, self.name.clone());
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn write_dot<const N: usize, R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; N],
+    params: WriteDotParams,
+    trace: Trace,
+) -> ChainResult<WriteDot<N>> {
+    WriteDot::new(graph, name, inputs, params, trace)
+}
@@ -0,0 +1,266 @@
+use serde::Deserialize;
+use truc::record::type_resolver::TypeResolver;
+
+use crate::{prelude::*, trace_filter};
+
+const EXTERNAL_INPUT_TRACE_NAME: &str = "external_input";
+const EXTERNAL_OUTPUT_TRACE_NAME: &str = "external_output";
+
+fn validate_external_name(name: &str, trace: impl FnOnce() -> Trace<'static>) -> ChainResult<()> {
+    syn::parse_str::<syn::Ident>(name)
+        .map(|_| ())
+        .map_err(|_| ChainError::Other {
+            msg: format!(r#"external name "{}" is not a valid identifier"#, name),
+            trace: trace(),
+        })
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalInputParams<'a> {
+    /// The name of the input in the `ExternalInputs` given to the chain `start` function.
+    name: &'a str,
+    fields: TypedFieldsParam<'a>,
+    order_fields: Option<DirectedFieldsParam<'a>>,
+    distinct_fields: Option<FieldsParam<'a>>,
+}
+
+#[derive(Getters)]
+pub struct ExternalInput {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; 0],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 1],
+    external_name: String,
+}
+
+impl ExternalInput {
+    fn new<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; 0],
+        params: ExternalInputParams,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        validate_external_name(params.name, || {
+            trace_filter!(trace, EXTERNAL_INPUT_TRACE_NAME)
+        })?;
+
+        let valid_fields = params
+            .fields
+            .validate_new(|| trace_filter!(trace, EXTERNAL_INPUT_TRACE_NAME))?;
+
+        let valid_order_fields = params
+            .order_fields
+            .map(|order_fields| {
+                order_fields.validate(
+                    |name| valid_fields.iter().any(|vf| vf.0.name() == name),
+                    || trace_filter!(trace, EXTERNAL_INPUT_TRACE_NAME),
+                )
+            })
+            .transpose()?;
+
+        let valid_distinct_fields = params
+            .distinct_fields
+            .map(|distinct_fields| {
+                distinct_fields.validate(
+                    |name| valid_fields.iter().any(|vf| vf.0.name() == name.name()),
+                    || trace_filter!(trace, EXTERNAL_INPUT_TRACE_NAME),
+                )
+            })
+            .transpose()?;
+
+        let mut streams = StreamsBuilder::new(&name, &inputs);
+        streams.new_main_stream(graph);
+
+        streams
+            .new_main_output(graph)
+            .update(|output_stream, facts_proof| {
+                {
+                    let mut output_stream_def = output_stream.record_definition().borrow_mut();
+                    for (name, r#type) in valid_fields.iter() {
+                        output_stream_def.add_dynamic_datum(name.name(), r#type.type_name());
+                    }
+                }
+                if let Some(order_fields) = valid_order_fields.as_ref() {
+                    output_stream.set_order_fact(
+                        order_fields
+                            .iter()
+                            .map(|field| field.as_ref().map(ValidFieldName::name)),
+                    );
+                }
+                if let Some(distinct_fields) = valid_distinct_fields.as_ref() {
+                    output_stream
+                        .set_distinct_fact(distinct_fields.iter().map(ValidFieldName::name));
+                }
+                Ok(facts_proof.order_facts_updated().distinct_facts_updated())
+            })?;
+
+        let outputs = streams.build();
+
+        Ok(Self {
+            name,
+            inputs,
+            outputs,
+            external_name: params.name.to_owned(),
+        })
+    }
+}
+
+impl DynNode for ExternalInput {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, _graph: &Graph, chain: &mut Chain) {
+        let thread_id = chain.new_threaded_source(
+            &self.name,
+            ChainThreadType::Regular,
+            &self.inputs,
+            &self.outputs,
+        );
+
+        chain.add_external_input(thread_id, &self.external_name, self.outputs.single());
+
+        let thread_body = quote! {
+            let output = thread_control.output_0.take().expect("output 0");
+            let mut input = thread_control.external_input.take().expect("external input");
+            move || {
+                while let Some(record) = input.next()? {
+                    output.send(Some(record))?;
+                }
+                output.send(None)?;
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread_id, &thread_body);
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn external_input<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 0],
+    params: ExternalInputParams,
+    trace: Trace,
+) -> ChainResult<ExternalInput> {
+    ExternalInput::new(graph, name, inputs, params, trace)
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalOutputParams<'a> {
+    /// The name of the output in the `ExternalOutputs` returned by the chain `start` function.
+    name: &'a str,
+}
+
+#[derive(Getters)]
+pub struct ExternalOutput {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; 1],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 0],
+    external_name: String,
+}
+
+impl ExternalOutput {
+    fn new<R: TypeResolver + Copy>(
+        _graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; 1],
+        params: ExternalOutputParams,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        validate_external_name(params.name, || {
+            trace_filter!(trace, EXTERNAL_OUTPUT_TRACE_NAME)
+        })?;
+
+        Ok(Self {
+            name,
+            inputs,
+            outputs: [],
+            external_name: params.name.to_owned(),
+        })
+    }
+}
+
+impl DynNode for ExternalOutput {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        let thread =
+            chain.get_thread_by_source(self.inputs.single(), &self.name, self.outputs.none());
+
+        let input = thread.format_input(
+            self.inputs.single().source(),
+            graph.chain_customizer(),
+            true,
+        );
+
+        let thread_id = thread.thread_id;
+
+        chain.add_external_output(thread_id, &self.external_name, self.inputs.single());
+
+        // Once the external output is dropped, the remaining records are still consumed so that
+        // the upstream threads can complete.
+        let thread_body = quote! {
+            let output = thread_control.external_output.take().expect("external output");
+
+            #input
+
+            move || {
+                let mut connected = true;
+                while let Some(record) = input.next()? {
+                    connected = connected && output.send(Some(record)).is_ok();
+                }
+                if connected {
+                    output.send(None).ok();
+                }
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread_id, &thread_body);
+
+        chain.set_thread_main(thread_id, self.name.clone());
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn external_output<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 1],
+    params: ExternalOutputParams,
+    trace: Trace,
+) -> ChainResult<ExternalOutput> {
+    ExternalOutput::new(graph, name, inputs, params, trace)
+}
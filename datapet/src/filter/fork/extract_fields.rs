@@ -10,6 +10,7 @@ const EXTRACT_FIELDS_TRACE_NAME: &str = "extract_fields";
 pub struct ExtractFieldsParams<'a> {
     #[serde(borrow)]
     fields: FieldsParam<'a>,
+    channel_capacity: Option<usize>,
 }
 
 #[derive(Getters)]
@@ -19,6 +20,7 @@ pub struct ExtractFields {
     inputs: [NodeStream; 1],
     #[getset(get = "pub")]
     outputs: [NodeStream; 2],
+    channel_capacity: Option<usize>,
 }
 
 impl ExtractFields {
@@ -75,6 +77,7 @@ impl ExtractFields {
             name,
             inputs,
             outputs,
+            channel_capacity: params.channel_capacity,
         })
     }
 }
@@ -93,6 +96,8 @@ impl DynNode for ExtractFields {
     }
 
     fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        chain.set_outputs_channel_capacity(&self.outputs, self.channel_capacity);
+
         let thread_id = chain.pipe_inputs(&self.name, &self.inputs, &self.outputs);
 
         let def_output_1 = chain.stream_definition_fragments(&self.outputs[1]);
@@ -2,6 +2,7 @@ use serde::Deserialize;
 use truc::record::type_resolver::TypeResolver;
 
 use crate::{
+    filter::sort::sort_if_unordered,
     graph::builder::{check_directed_order_starts_with, check_distinct_eq},
     prelude::*,
     support::cmp::fields_cmp_ab,
@@ -16,6 +17,7 @@ pub struct JoinParams<'a> {
     #[serde(borrow)]
     primary_fields: FieldsParam<'a>,
     secondary_fields: FieldsParam<'a>,
+    channel_capacity: Option<usize>,
 }
 
 #[derive(Getters)]
@@ -28,6 +30,12 @@ pub struct Join {
     primary_fields: Vec<ValidFieldName>,
     secondary_fields: Vec<ValidFieldName>,
     joined_fields: Vec<String>,
+    channel_capacity: Option<usize>,
+    /// The sorts inserted before the join in auto-order mode.
+    ///
+    /// The inputs are not deduplicated: a join on an input which is not distinct on its fields
+    /// still fails with [`ChainError::ExpectedDistinct`] rather than silently dropping records.
+    auto_order_nodes: Vec<Box<dyn DynNode>>,
 }
 
 impl Join {
@@ -38,8 +46,6 @@ impl Join {
         params: JoinParams,
         trace: Trace,
     ) -> ChainResult<Self> {
-        let mut streams = StreamsBuilder::new(&name, &inputs);
-
         let valid_primary_fields =
             params
                 .primary_fields
@@ -50,6 +56,84 @@ impl Join {
                 .secondary_fields
                 .validate_on_stream(&inputs[1], graph, || trace_filter!(trace, JOIN_TRACE_NAME))?;
 
+        let mut auto_order_nodes = Vec::<Box<dyn DynNode>>::new();
+        let [primary_input, secondary_input] = inputs;
+        let mut ordered_inputs = Vec::with_capacity(2);
+        for (stream_name, stream_info, input, fields) in [
+            (
+                "primary",
+                "primary stream",
+                primary_input,
+                &valid_primary_fields,
+            ),
+            (
+                "secondary",
+                "secondary stream",
+                secondary_input,
+                &valid_secondary_fields,
+            ),
+        ] {
+            let expected_fact_fields = {
+                let input_stream_def = graph
+                    .get_stream(input.record_type())
+                    .expect("input_stream_def")
+                    .borrow();
+                input_stream_def[input.variant_id()]
+                    .data()
+                    .filter_map(|d| {
+                        let datum = &input_stream_def[d];
+                        fields
+                            .iter()
+                            .find(|field| field.name() == datum.name())
+                            .cloned()
+                    })
+                    .collect::<Vec<ValidFieldName>>()
+            };
+            let expected_fact_datum_ids = |graph: &GraphBuilder<R>, input: &NodeStream| {
+                let input_stream_def = graph
+                    .get_stream(input.record_type())
+                    .expect("input_stream_def")
+                    .borrow();
+                expected_fact_fields
+                    .iter()
+                    .map(|field| {
+                        input_stream_def
+                            .get_current_datum_definition_by_name(field.name())
+                            .expect("datum")
+                            .id()
+                    })
+                    .collect::<Vec<_>>()
+            };
+
+            let (input, auto_sort) = sort_if_unordered(
+                graph,
+                name.sub(format!("{}_{}_auto_sort", name.to_name(), stream_name)),
+                input,
+                &expected_fact_fields
+                    .iter()
+                    .map(|field| Directed::Ascending(field.name()))
+                    .collect::<Vec<_>>(),
+                |graph, input| {
+                    check_directed_order_starts_with(
+                        &expected_fact_datum_ids(graph, input),
+                        input.facts().order(),
+                        &graph
+                            .get_stream(input.record_type())
+                            .expect("input_stream_def")
+                            .borrow(),
+                        stream_info,
+                        || trace_filter!(trace, JOIN_TRACE_NAME),
+                    )
+                },
+                trace.clone(),
+            )?;
+            auto_order_nodes.extend(auto_sort.map(|sort| Box::new(sort) as Box<dyn DynNode>));
+            ordered_inputs.push(input);
+        }
+        let inputs: [NodeStream; 2] = ordered_inputs.try_into().expect("two inputs");
+
+        let mut streams = StreamsBuilder::new(&name, &inputs);
+
         let joined_fields =
             streams
                 .output_from_input(0, true, graph)
@@ -127,6 +211,8 @@ impl Join {
             primary_fields: valid_primary_fields,
             secondary_fields: valid_secondary_fields,
             joined_fields,
+            channel_capacity: params.channel_capacity,
+            auto_order_nodes,
         })
     }
 }
@@ -144,7 +230,13 @@ impl DynNode for Join {
         &self.outputs
     }
 
-    fn gen_chain(&self, _graph: &Graph, chain: &mut Chain) {
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        for node in &self.auto_order_nodes {
+            node.gen_chain(graph, chain);
+        }
+
+        chain.set_outputs_channel_capacity(&self.outputs, self.channel_capacity);
+
         let thread_id = chain.pipe_inputs(&self.name, &self.inputs, &self.outputs);
 
         let primary_input_def = chain.stream_definition_fragments(&self.inputs[0]);
@@ -252,7 +344,12 @@ impl DynNode for Join {
     }
 
     fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
-        Box::new(Some(self as &dyn DynNode).into_iter())
+        Box::new(
+            self.auto_order_nodes
+                .iter()
+                .flat_map(|node| node.all_nodes())
+                .chain(Some(self as &dyn DynNode)),
+        )
     }
 }
 
@@ -265,3 +362,65 @@ pub fn join<R: TypeResolver + Copy>(
 ) -> ChainResult<Join> {
     Join::new(graph, name, inputs, params, trace)
 }
+
+#[cfg(test)]
+mod tests {
+    use truc::record::type_resolver::StaticTypeResolver;
+
+    use super::join;
+    use crate::{filter::function::produce::function_produce, prelude::*};
+
+    #[test]
+    fn should_not_dedup_join_input_in_auto_order() {
+        let type_resolver = {
+            let mut resolver = StaticTypeResolver::new();
+            resolver.add_std_types();
+            resolver
+        };
+        let mut graph = GraphBuilder::new(&type_resolver, ChainCustomizer::default());
+        graph.set_auto_order(true);
+        let name = FullyQualifiedName::new("main");
+
+        let params = graph
+            .params()
+            .from_ron_str(
+                r##"(
+                    fields: [("num", "u8")],
+                    body: r#"{ output.send(None)?; Ok(()) }"#,
+                    distinct_fields: Some(["num"]),
+                )"##,
+            )
+            .unwrap();
+        let primary =
+            function_produce(&mut graph, name.sub("primary"), [], params, Trace::root()).unwrap();
+        let params = graph
+            .params()
+            .from_ron_str(
+                r##"(
+                    fields: [("other_num", "u8")],
+                    body: r#"{ output.send(None)?; Ok(()) }"#,
+                )"##,
+            )
+            .unwrap();
+        let secondary =
+            function_produce(&mut graph, name.sub("secondary"), [], params, Trace::root()).unwrap();
+
+        let params = graph
+            .params()
+            .from_ron_str(r#"(primary_fields: ["num"], secondary_fields: ["other_num"])"#)
+            .unwrap();
+        let result = join(
+            &mut graph,
+            name.sub("join"),
+            [primary.outputs()[0].clone(), secondary.outputs()[0].clone()],
+            params,
+            Trace::root(),
+        );
+
+        // The unordered inputs are sorted, but the secondary one is not distinct
+        assert!(matches!(
+            result,
+            Err(ChainError::ExpectedDistinct { more_info, .. }) if more_info == "secondary stream"
+        ));
+    }
+}
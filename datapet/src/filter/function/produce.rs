@@ -13,6 +13,7 @@ pub struct FunctionProduceParams<'a> {
     order_fields: Option<DirectedFieldsParam<'a>>,
     distinct_fields: Option<FieldsParam<'a>>,
     body: &'a str,
+    channel_capacity: Option<usize>,
 }
 
 #[derive(Getters)]
@@ -24,6 +25,7 @@ pub struct FunctionProduce {
     outputs: [NodeStream; 1],
     fields: Vec<(ValidFieldName, ValidFieldType)>,
     body: TokenStream,
+    channel_capacity: Option<usize>,
 }
 
 impl FunctionProduce {
@@ -102,6 +104,7 @@ impl FunctionProduce {
             outputs,
             fields: valid_fields,
             body: valid_body,
+            channel_capacity: params.channel_capacity,
         })
     }
 }
@@ -120,6 +123,8 @@ impl DynNode for FunctionProduce {
     }
 
     fn gen_chain(&self, _graph: &Graph, chain: &mut Chain) {
+        chain.set_outputs_channel_capacity(&self.outputs, self.channel_capacity);
+
         let thread_id = chain.new_threaded_source(
             &self.name,
             ChainThreadType::Regular,
@@ -4,6 +4,7 @@ use serde::Deserialize;
 use truc::record::{definition::DatumId, type_resolver::TypeResolver};
 
 use crate::{
+    filter::sort::{sort_if_unordered, Sort},
     graph::builder::check_undirected_order_starts_with,
     prelude::*,
     support::eq::{fields_eq, fields_eq_ab},
@@ -29,6 +30,8 @@ pub struct Group {
     group_field: ValidFieldName,
     group_stream: NodeSubStream,
     fields: Vec<ValidFieldName>,
+    /// The sort inserted before the group in auto-order mode.
+    auto_sort: Option<Sort>,
 }
 
 impl Group {
@@ -51,6 +54,52 @@ impl Group {
             }
         })?;
 
+        let [input] = inputs;
+        let group_by_fields = {
+            let def = graph
+                .get_stream(input.record_type())
+                .unwrap_or_else(|| panic!(r#"stream "{}""#, input.record_type()))
+                .borrow();
+            def[input.variant_id()]
+                .data()
+                .map(|d| def[d].name())
+                .filter(|field| !valid_fields.iter().any(|valid| valid.name() == *field))
+                .map(str::to_owned)
+                .collect::<Vec<String>>()
+        };
+        let (input, auto_sort) = sort_if_unordered(
+            graph,
+            name.sub(format!("{}_auto_sort", name.to_name())),
+            input,
+            &group_by_fields
+                .iter()
+                .map(|field| Directed::Ascending(field.as_str()))
+                .collect::<Vec<_>>(),
+            |graph, input| {
+                let def = graph
+                    .get_stream(input.record_type())
+                    .unwrap_or_else(|| panic!(r#"stream "{}""#, input.record_type()))
+                    .borrow();
+                let group_by_datum_ids = group_by_fields
+                    .iter()
+                    .map(|field| {
+                        def.get_current_datum_definition_by_name(field)
+                            .expect("datum")
+                            .id()
+                    })
+                    .collect::<Vec<_>>();
+                check_undirected_order_starts_with(
+                    &group_by_datum_ids,
+                    input.facts().order(),
+                    &*def,
+                    "main stream",
+                    || trace_filter!(trace, GROUP_TRACE_NAME),
+                )
+            },
+            trace.clone(),
+        )?;
+        let inputs = [input];
+
         let mut streams = StreamsBuilder::new(&name, &inputs);
         streams.new_named_stream("group", graph);
 
@@ -152,6 +201,7 @@ impl Group {
             group_field: valid_group_field,
             group_stream,
             fields: valid_fields,
+            auto_sort,
         })
     }
 }
@@ -170,6 +220,10 @@ impl DynNode for Group {
     }
 
     fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        if let Some(auto_sort) = &self.auto_sort {
+            auto_sort.gen_chain(graph, chain);
+        }
+
         let def_input = chain.stream_definition_fragments(self.inputs.single());
         let def = chain.stream_definition_fragments(self.outputs.single());
         let def_group = chain.sub_stream_definition_fragments(&self.group_stream);
@@ -243,7 +297,12 @@ impl DynNode for Group {
     }
 
     fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
-        Box::new(Some(self as &dyn DynNode).into_iter())
+        Box::new(
+            self.auto_sort
+                .iter()
+                .map(|sort| sort as &dyn DynNode)
+                .chain(Some(self as &dyn DynNode)),
+        )
     }
 }
 
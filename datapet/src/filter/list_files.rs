@@ -0,0 +1,179 @@
+use datapet_support::iterator::io::compression::Compression;
+use serde::Deserialize;
+use truc::record::type_resolver::TypeResolver;
+
+use crate::{prelude::*, support::compression::file_compression, trace_filter};
+
+const LIST_FILES_TRACE_NAME: &str = "list_files";
+
+const PATH_FIELD: &str = "path";
+const SIZE_FIELD: &str = "size";
+const MODIFIED_FIELD: &str = "modified";
+const CONTENT_FIELD: &str = "content";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ListFilesParams<'a> {
+    root: &'a str,
+    pattern: Option<&'a str>,
+    follow_symlinks: Option<bool>,
+    content: Option<bool>,
+    /// The compression of the content, detected from the first bytes of each file if not
+    /// specified.
+    compression: Option<Compression>,
+    channel_capacity: Option<usize>,
+}
+
+#[derive(Getters)]
+pub struct ListFiles {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; 0],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 1],
+    root: String,
+    pattern: String,
+    follow_symlinks: bool,
+    content: bool,
+    compression: Option<Compression>,
+    channel_capacity: Option<usize>,
+}
+
+impl ListFiles {
+    fn new<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; 0],
+        params: ListFilesParams,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        let pattern = params.pattern.unwrap_or("**/*");
+        glob::Pattern::new(pattern).map_err(|err| ChainError::Other {
+            msg: format!("Invalid pattern {}: {}", pattern, err),
+            trace: trace_filter!(trace, LIST_FILES_TRACE_NAME),
+        })?;
+        let content = params.content.unwrap_or(false);
+
+        let mut streams = StreamsBuilder::new(&name, &inputs);
+        streams.new_main_stream(graph);
+
+        streams
+            .new_main_output(graph)
+            .update(|output_stream, facts_proof| {
+                {
+                    let mut output_stream_def = output_stream.record_definition().borrow_mut();
+                    output_stream_def.add_dynamic_datum(PATH_FIELD, "Box<str>");
+                    output_stream_def.add_dynamic_datum(SIZE_FIELD, "u64");
+                    output_stream_def.add_dynamic_datum(MODIFIED_FIELD, "u64");
+                    if content {
+                        output_stream_def.add_dynamic_datum(CONTENT_FIELD, "Box<str>");
+                    }
+                }
+                // The traversal is sorted, and a path is listed only once.
+                output_stream.set_order_fact([Directed::Ascending(PATH_FIELD)]);
+                output_stream.set_distinct_fact([PATH_FIELD]);
+                Ok(facts_proof.order_facts_updated().distinct_facts_updated())
+            })?;
+
+        let outputs = streams.build();
+
+        Ok(Self {
+            name,
+            inputs,
+            outputs,
+            root: params.root.to_owned(),
+            pattern: pattern.to_owned(),
+            follow_symlinks: params.follow_symlinks.unwrap_or(false),
+            content,
+            compression: params.compression,
+            channel_capacity: params.channel_capacity,
+        })
+    }
+}
+
+impl DynNode for ListFiles {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        chain.set_outputs_channel_capacity(&self.outputs, self.channel_capacity);
+
+        let thread_id = chain.new_threaded_source(
+            &self.name,
+            ChainThreadType::Regular,
+            &self.inputs,
+            &self.outputs,
+        );
+
+        let def = chain.stream_definition_fragments(self.outputs.single());
+        let record = def.record();
+        let unpacked_record = def.unpacked_record();
+
+        let error_type = graph.chain_customizer().error_type.to_name();
+
+        let root = &self.root;
+        let pattern = &self.pattern;
+        let follow_symlinks = self.follow_symlinks;
+
+        let path_field = format_ident!("{}", PATH_FIELD);
+        let size_field = format_ident!("{}", SIZE_FIELD);
+        let modified_field = format_ident!("{}", MODIFIED_FIELD);
+        let content = self.content.then(|| {
+            let content_field = format_ident!("{}", CONTENT_FIELD);
+            let compression = file_compression(self.compression);
+            quote! {
+                #content_field: file.read_content(#compression).map_err(|err| #error_type::custom(err.to_string()))?,
+            }
+        });
+
+        let thread_body = quote! {
+            let output = thread_control.output_0.take().expect("output 0");
+            move || {
+                use datapet_support::iterator::io::fs::{ListFiles, Pattern};
+                use fallible_iterator::FallibleIterator;
+
+                let mut files = ListFiles::new(
+                    #root,
+                    Pattern::new(#pattern).expect("pattern"),
+                    #follow_symlinks,
+                );
+                while let Some(file) = files.next().map_err(|err| #error_type::custom(err.to_string()))? {
+                    let record = #record::new(#unpacked_record {
+                        #content
+                        #path_field: file.path,
+                        #size_field: file.size,
+                        #modified_field: file.modified,
+                    });
+                    output.send(Some(record))?;
+                }
+                output.send(None)?;
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread_id, &thread_body);
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn list_files<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 0],
+    params: ListFilesParams,
+    trace: Trace,
+) -> ChainResult<ListFiles> {
+    ListFiles::new(graph, name, inputs, params, trace)
+}
@@ -1,12 +1,18 @@
 pub mod accumulate;
 pub mod anchor;
+pub mod arrow;
 pub mod debug;
 pub mod dedup;
+pub mod dot;
+pub mod external;
 pub mod fork;
 pub mod function;
 pub mod group;
 pub mod hof;
+pub mod list_files;
 pub mod monitor;
+pub mod parallel_map;
 pub mod sort;
+pub mod sqlite;
 pub mod transform;
 pub mod unwrap;
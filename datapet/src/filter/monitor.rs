@@ -1,3 +1,15 @@
+//! The `monitor` background thread, which reports the memory and stream metrics of the chain
+//! every second.
+//!
+//! * `dtpt_monitor.csv` has one row per report with the memory totals of the process:
+//!   `Timestamp,memory_rss,memory_swap,memory_object,memory_wrapped`.
+//! * `dtpt_monitor_groups.csv` is only written when the chain runs with `memory_groups`. It is in
+//!   long format rather than one column per node, since the allocation groups are registered as
+//!   the threads start: one `Timestamp,group,memory_object` row per node and report. The memory
+//!   of the nodes beyond the tracked groups is only accounted in `dtpt_monitor.csv`.
+//! * `dtpt_monitor_streams.csv` is only written when the chain runs with `stream_metrics`, with
+//!   one row per stream and report.
+
 use datapet_codegen_macro::dtpt_internal;
 
 dtpt_internal!(inline(
@@ -27,6 +39,13 @@ pub monitor()
             let mut csv = File::create("dtpt_monitor.csv").unwrap();
             write!(csv, "Timestamp,memory_rss,memory_swap,memory_object,memory_wrapped\n").unwrap();
 
+            // Only created if the chain registers allocation groups, one row per group and report
+            // since groups are registered as the chain threads start
+            let mut groups_csv = None::<File>;
+
+            // Only created if the chain records stream metrics
+            let mut streams_csv = None::<File>;
+
             let mut meter = self_meter::Meter::new(Duration::from_millis(1000)).unwrap();
             meter.track_current_thread("main");
 
@@ -39,16 +58,69 @@ pub monitor()
 
                 if let Some(report) = report.as_ref() {
                     let timestamp = DateTime::<Utc>::from(report.timestamp);
-                    let data = crate::dtpt_monitor::DATA.lock().unwrap();
+                    // Do not allocate while holding the lock, the tracker needs it
+                    let (object, wrapped) = {
+                        let data = crate::dtpt_monitor::DATA.lock().unwrap();
+                        (data.object, data.wrapped)
+                    };
                     write!(
                         csv,
                         "{},{},{},{},{}\n",
                         timestamp,
                         report.memory_rss,
                         report.memory_swap,
-                        data.object,
-                        data.wrapped,
+                        object,
+                        wrapped,
                     ).unwrap();
+
+                    // Nodes of several chains may share the same name
+                    let mut groups = Vec::<(String, isize)>::new();
+                    for (id, name) in crate::dtpt_monitor::GROUP_NAMES.lock().unwrap().iter() {
+                        // Groups beyond the tracked ones are only accounted in the totals
+                        if let Some(object) = crate::dtpt_monitor::group_object(*id) {
+                            match groups.iter_mut().find(|(group_name, _)| group_name == name) {
+                                Some((_, group_object)) => *group_object += object,
+                                None => groups.push((name.clone(), object)),
+                            }
+                        }
+                    }
+                    if !groups.is_empty() {
+                        let groups_csv = groups_csv.get_or_insert_with(|| {
+                            let mut groups_csv = File::create("dtpt_monitor_groups.csv").unwrap();
+                            write!(groups_csv, "Timestamp,group,memory_object\n").unwrap();
+                            groups_csv
+                        });
+                        for (name, object) in groups {
+                            write!(groups_csv, "{},{},{}\n", timestamp, name, object).unwrap();
+                        }
+                    }
+
+                    let streams = thread_control.chain_configuration.metrics.snapshot();
+                    if !streams.is_empty() {
+                        let streams_csv = streams_csv.get_or_insert_with(|| {
+                            let mut streams_csv = File::create("dtpt_monitor_streams.csv").unwrap();
+                            write!(
+                                streams_csv,
+                                "Timestamp,name,capacity,records_produced,records_sent,records_received,send_time_ms,recv_time_ms,queued\n",
+                            ).unwrap();
+                            streams_csv
+                        });
+                        for (name, stream) in streams {
+                            write!(
+                                streams_csv,
+                                "{},{},{},{},{},{},{},{},{}\n",
+                                timestamp,
+                                name,
+                                stream.capacity,
+                                stream.records_produced,
+                                stream.records_sent,
+                                stream.records_received,
+                                stream.send_time.as_millis(),
+                                stream.recv_time.as_millis(),
+                                stream.queued,
+                            ).unwrap();
+                        }
+                    }
                 }
 
                 //println!("Report: {:#?}", report);
@@ -0,0 +1,202 @@
+use proc_macro2::TokenStream;
+use serde::Deserialize;
+use truc::record::type_resolver::TypeResolver;
+
+use crate::{prelude::*, trace_filter};
+
+const PARALLEL_MAP_TRACE_NAME: &str = "parallel_map";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ParallelMapParams<'a> {
+    /// Updates `record`, a `&mut` reference, and returns a `Result<(), Error>`.
+    body: &'a str,
+    /// The number of worker threads, the available parallelism if not set.
+    workers: Option<usize>,
+    /// Keeps the order of the records, `true` by default.
+    ordered: Option<bool>,
+}
+
+#[derive(Getters)]
+pub struct ParallelMap {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; 1],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 1],
+    body: TokenStream,
+    workers: Option<usize>,
+    ordered: bool,
+}
+
+impl ParallelMap {
+    fn new<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; 1],
+        params: ParallelMapParams,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        let valid_body =
+            params
+                .body
+                .parse::<TokenStream>()
+                .map_err(|err| ChainError::InvalidTokenStream {
+                    name: "body".to_owned(),
+                    msg: err.to_string(),
+                    trace: trace_filter!(trace, PARALLEL_MAP_TRACE_NAME),
+                })?;
+
+        if params.workers == Some(0) {
+            return Err(ChainError::Other {
+                msg: "workers must be greater than 0".to_owned(),
+                trace: trace_filter!(trace, PARALLEL_MAP_TRACE_NAME),
+            });
+        }
+
+        let ordered = params.ordered.unwrap_or(true);
+
+        let mut streams = StreamsBuilder::new(&name, &inputs);
+        streams
+            .output_from_input(0, true, graph)
+            .pass_through(|builder, facts_proof| {
+                if !ordered {
+                    builder.set_order_fact(std::iter::empty::<Directed<&str>>());
+                }
+                facts_proof.order_facts_updated().distinct_facts_updated()
+            });
+        let outputs = streams.build();
+        Ok(Self {
+            name,
+            inputs,
+            outputs,
+            body: valid_body,
+            workers: params.workers,
+            ordered,
+        })
+    }
+}
+
+impl DynNode for ParallelMap {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        let thread_id = chain.pipe_inputs(&self.name, &self.inputs, &self.outputs);
+
+        let record = chain
+            .stream_definition_fragments(self.outputs.single())
+            .record();
+
+        let customizer = graph.chain_customizer();
+        let error_type = customizer.error_type.to_name();
+
+        let name = self.name.to_string();
+        let default_workers = if let Some(workers) = self.workers {
+            quote! { #workers }
+        } else {
+            quote! {
+                std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+            }
+        };
+        let ordered = self.ordered;
+        let body = &self.body;
+
+        // The workers run like the thread of the node, in its allocation group and tracing span
+        let mut run_worker = quote! { work() };
+        if customizer.memory_groups {
+            run_worker = quote! {
+                crate::dtpt_monitor::run_in_group(&format!("{}[{}]", #name, worker), || #run_worker)
+            };
+        }
+        let span = if customizer.tracing {
+            run_worker = quote! {
+                let span = datapet_support::tracing::info_span!(parent: &span, "worker", worker);
+                let _entered = span.enter();
+                #run_worker
+            };
+            Some(quote! {
+                let span = datapet_support::tracing::Span::current();
+            })
+        } else {
+            None
+        };
+        let worker = if customizer.memory_groups || customizer.tracing {
+            quote! { worker }
+        } else {
+            quote! { _ }
+        };
+        let (metrics, count_record) = if customizer.stream_metrics {
+            (
+                Some(quote! {
+                    let metrics = thread_control.chain_configuration.metrics.stream_metrics(#name);
+                }),
+                Some(quote! {
+                    metrics.record_produced();
+                }),
+            )
+        } else {
+            (None, None)
+        };
+
+        let thread_body = quote! {
+            let workers = thread_control
+                .chain_configuration
+                .worker_count(#name, #default_workers);
+            #metrics
+            move || {
+                let input = datapet_support::iterator::sync::mpsc::Receive::<_, #error_type, _>::new(
+                    thread_control.input_0.take().expect("input 0"),
+                );
+                let output = thread_control.output_0.take().expect("output 0");
+                let map = |record: &mut #record| -> Result<(), #error_type> { #body };
+                #span
+                datapet_support::iterator::parallel::parallel_map(
+                    input,
+                    workers,
+                    #ordered,
+                    &*thread_control.cancellation,
+                    |#worker, work: &mut dyn FnMut()| {
+                        #run_worker
+                    },
+                    |record: &mut #record| {
+                        map(record)?;
+                        #count_record
+                        Ok(())
+                    },
+                    |record| {
+                        output.send(Some(record))?;
+                        Ok(())
+                    },
+                )?;
+                output.send(None)?;
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread_id, &thread_body);
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn parallel_map<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 1],
+    params: ParallelMapParams,
+    trace: Trace,
+) -> ChainResult<ParallelMap> {
+    ParallelMap::new(graph, name, inputs, params, trace)
+}
@@ -1,7 +1,13 @@
+use datapet_support::data::buffer::BufferCompression;
+use itertools::Itertools;
 use serde::Deserialize;
 use truc::record::type_resolver::TypeResolver;
 
-use crate::{prelude::*, support::cmp::fields_cmp, trace_filter};
+use crate::{
+    prelude::*,
+    support::{buffer::buffer_options, cmp::fields_cmp},
+    trace_filter,
+};
 
 const SORT_TRACE_NAME: &str = "sort";
 
@@ -10,6 +16,13 @@ const SORT_TRACE_NAME: &str = "sort";
 pub struct SortParams<'a> {
     #[serde(borrow)]
     fields: DirectedFieldsParam<'a>,
+    /// Sorts and spills the slices on that many worker threads.
+    threads: Option<usize>,
+    /// The maximum size in bytes of the records sorted in memory.
+    memory_budget: Option<usize>,
+    /// The compression of the spilled slices.
+    compression: Option<BufferCompression>,
+    channel_capacity: Option<usize>,
 }
 
 #[derive(Getters)]
@@ -20,6 +33,12 @@ pub struct Sort {
     #[getset(get = "pub")]
     outputs: [NodeStream; 1],
     fields: Vec<Directed<ValidFieldName>>,
+    threads: Option<usize>,
+    memory_budget: Option<usize>,
+    compression: BufferCompression,
+    channel_capacity: Option<usize>,
+    /// In auto-order mode, the input is already ordered by the fields of the sort.
+    pass_through: bool,
 }
 
 impl Sort {
@@ -36,15 +55,51 @@ impl Sort {
                 trace_filter!(trace, SORT_TRACE_NAME)
             })?;
 
+        if params.threads == Some(0) {
+            return Err(ChainError::Other {
+                msg: "threads must be greater than 0".to_owned(),
+                trace: trace_filter!(trace, SORT_TRACE_NAME),
+            });
+        }
+
+        let pass_through = graph.auto_order() && {
+            let input = inputs.single();
+            let def = graph
+                .get_stream(input.record_type())
+                .unwrap_or_else(|| panic!(r#"stream "{}""#, input.record_type()))
+                .borrow();
+            let order = input.facts().order();
+            order.len() >= valid_fields.len()
+                && valid_fields.iter().zip(order).all(|(field, datum_id)| {
+                    field.as_ref().map(|field| {
+                        def.get_current_datum_definition_by_name(field.name())
+                            .expect("datum")
+                            .id()
+                    }) == *datum_id
+                })
+        };
+        if pass_through {
+            graph.warn(format!(
+                r#"sort "{}" does nothing, its input is already ordered by [{}]"#,
+                name,
+                valid_fields
+                    .iter()
+                    .map(|field| field.as_ref().map(ValidFieldName::name))
+                    .join(", ")
+            ));
+        }
+
         let mut streams = StreamsBuilder::new(&name, &inputs);
         streams
             .output_from_input(0, true, graph)
             .pass_through(|builder, facts_proof| {
-                builder.set_order_fact(
-                    valid_fields
-                        .iter()
-                        .map(|field| field.as_ref().map(ValidFieldName::name)),
-                );
+                if !pass_through {
+                    builder.set_order_fact(
+                        valid_fields
+                            .iter()
+                            .map(|field| field.as_ref().map(ValidFieldName::name)),
+                    );
+                }
                 facts_proof.order_facts_updated().distinct_facts_updated()
             });
         let outputs = streams.build();
@@ -53,6 +108,11 @@ impl Sort {
             inputs,
             outputs,
             fields: valid_fields,
+            threads: params.threads,
+            memory_budget: params.memory_budget,
+            compression: params.compression.unwrap_or_default(),
+            channel_capacity: params.channel_capacity,
+            pass_through,
         })
     }
 }
@@ -71,6 +131,21 @@ impl DynNode for Sort {
     }
 
     fn gen_chain(&self, _graph: &Graph, chain: &mut Chain) {
+        chain.set_outputs_channel_capacity(&self.outputs, self.channel_capacity);
+
+        if self.pass_through {
+            chain.implement_inline_node(
+                self,
+                self.inputs.single(),
+                self.outputs.single(),
+                &quote! {
+                    #[allow(clippy::let_and_return)]
+                    input
+                },
+            );
+            return;
+        }
+
         let record = chain
             .stream_definition_fragments(self.outputs.single())
             .record();
@@ -82,8 +157,29 @@ impl DynNode for Sort {
                 .map(|field| field.as_ref().map(ValidFieldName::name)),
         );
 
-        let inline_body = quote! {
-            datapet_support::iterator::sort::Sort::new(input, #cmp)
+        let name = self.name.to_string();
+        let memory_budget = if let Some(memory_budget) = self.memory_budget {
+            quote! { Some(#memory_budget) }
+        } else {
+            quote! { None }
+        };
+        let buffer_options = buffer_options(&self.name, self.compression);
+        let options = quote! {
+            datapet_support::iterator::sort::SortOptions {
+                memory_budget: chain_configuration.sort_memory_budget(#name, #memory_budget),
+                buffer: #buffer_options,
+                ..Default::default()
+            }
+        };
+
+        let inline_body = if let Some(threads) = self.threads {
+            quote! {
+                datapet_support::iterator::sort::ParallelSort::with_options(input, #cmp, #threads, #options)
+            }
+        } else {
+            quote! {
+                datapet_support::iterator::sort::Sort::with_options(input, #cmp, #options)
+            }
         };
 
         chain.implement_inline_node(
@@ -109,6 +205,46 @@ pub fn sort<R: TypeResolver + Copy>(
     Sort::new(graph, name, inputs, params, trace)
 }
 
+/// Sorts the input of a filter on `fields` in auto-order mode, when `check` reports it is not
+/// in the order the filter expects.
+///
+/// Returns the stream the filter should read, and the sort to generate before the filter, if
+/// any.
+pub(crate) fn sort_if_unordered<R, C>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    input: NodeStream,
+    fields: &[Directed<&str>],
+    check: C,
+    trace: Trace,
+) -> ChainResult<(NodeStream, Option<Sort>)>
+where
+    R: TypeResolver + Copy,
+    C: FnOnce(&GraphBuilder<R>, &NodeStream) -> ChainResult<()>,
+{
+    match check(graph, &input) {
+        Ok(()) => Ok((input, None)),
+        Err(ChainError::ExpectedMinimalOrder { .. }) if graph.auto_order() => {
+            let params = SortParams {
+                fields: fields.to_vec().into(),
+                threads: None,
+                memory_budget: None,
+                compression: None,
+                channel_capacity: None,
+            };
+            let sort = Sort::new(graph, name, [input], params, trace)?;
+            graph.warn(format!(
+                r#"sort "{}" on [{}] inserted by auto-order"#,
+                sort.name,
+                fields.iter().join(", ")
+            ));
+            let sorted = sort.outputs.single().clone();
+            Ok((sorted, Some(sort)))
+        }
+        Err(err) => Err(err),
+    }
+}
+
 const SUB_SORT_TRACE_NAME: &str = "sub_sort";
 
 #[derive(Deserialize, Debug)]
@@ -119,6 +255,10 @@ pub struct SubSortParams<'a> {
     fields: DirectedFieldsParam<'a>,
 }
 
+/// Sorts the records of a sub-stream within each record.
+///
+/// The sub-records already are in memory, so they are sorted there: unlike [`Sort`], the
+/// `sort_memory_budget` and `spill_dir` of the chain configuration do not apply.
 #[derive(Getters)]
 pub struct SubSort {
     name: FullyQualifiedName,
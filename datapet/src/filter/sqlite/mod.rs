@@ -0,0 +1,154 @@
+use proc_macro2::TokenStream;
+
+pub mod read;
+pub mod write;
+
+/// SQLite column information of a record field.
+#[derive(PartialEq, Eq, Debug)]
+pub struct SqliteColumn {
+    /// The SQLite type affinity of the column.
+    pub sql_type: &'static str,
+    pub nullable: bool,
+    /// The type to read from rows, which may need a conversion into the field type.
+    pub read_type: &'static str,
+    pub read_into: bool,
+}
+
+impl SqliteColumn {
+    pub fn column_definition(&self, name: &str) -> String {
+        format!(
+            "{} {}{}",
+            quote_identifier(name),
+            self.sql_type,
+            if self.nullable { "" } else { " NOT NULL" }
+        )
+    }
+
+    /// Generates the expression reading the column at `index` from a `rusqlite::Row` named
+    /// `row`, errors being converted by a function named `sqlite_error`.
+    pub fn read_expr(&self, index: usize) -> TokenStream {
+        let read_type = syn::parse_str::<syn::Type>(self.read_type).expect("read_type");
+        let read_type = if self.nullable {
+            quote! { Option<#read_type> }
+        } else {
+            quote! { #read_type }
+        };
+        let convert = match (self.nullable, self.read_into) {
+            (_, false) => None,
+            (false, true) => Some(quote! { .into() }),
+            (true, true) => Some(quote! { .map(Into::into) }),
+        };
+        quote! { row.get::<_, #read_type>(#index).map_err(sqlite_error)? #convert }
+    }
+}
+
+/// Maps a field type to a SQLite column, `None` if the type is not supported.
+pub fn sqlite_column(type_name: &str) -> Option<SqliteColumn> {
+    let type_name = type_name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let (type_name, nullable) = match type_name
+        .strip_prefix("Option<")
+        .and_then(|t| t.strip_suffix('>'))
+    {
+        Some(inner) => (inner, true),
+        None => (type_name.as_str(), false),
+    };
+    let (sql_type, read_type, read_into) = match type_name {
+        "bool" => ("INTEGER", "bool", false),
+        "i8" => ("INTEGER", "i8", false),
+        "i16" => ("INTEGER", "i16", false),
+        "i32" => ("INTEGER", "i32", false),
+        "i64" => ("INTEGER", "i64", false),
+        "isize" => ("INTEGER", "isize", false),
+        "u8" => ("INTEGER", "u8", false),
+        "u16" => ("INTEGER", "u16", false),
+        "u32" => ("INTEGER", "u32", false),
+        "u64" => ("INTEGER", "u64", false),
+        "usize" => ("INTEGER", "usize", false),
+        "f32" => ("REAL", "f32", false),
+        "f64" => ("REAL", "f64", false),
+        "String" => ("TEXT", "String", false),
+        "Box<str>" => ("TEXT", "String", true),
+        "Vec<u8>" => ("BLOB", "Vec<u8>", false),
+        "Box<[u8]>" => ("BLOB", "Vec<u8>", true),
+        _ => return None,
+    };
+    Some(SqliteColumn {
+        sql_type,
+        nullable,
+        read_type,
+        read_into,
+    })
+}
+
+pub fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote_identifier, sqlite_column, SqliteColumn};
+
+    #[test]
+    fn test_sqlite_column_mapping() {
+        assert_eq!(
+            sqlite_column("u32"),
+            Some(SqliteColumn {
+                sql_type: "INTEGER",
+                nullable: false,
+                read_type: "u32",
+                read_into: false,
+            })
+        );
+        assert_eq!(
+            sqlite_column("Option<f64>"),
+            Some(SqliteColumn {
+                sql_type: "REAL",
+                nullable: true,
+                read_type: "f64",
+                read_into: false,
+            })
+        );
+        assert_eq!(
+            sqlite_column("Option < Box < str > >"),
+            Some(SqliteColumn {
+                sql_type: "TEXT",
+                nullable: true,
+                read_type: "String",
+                read_into: true,
+            })
+        );
+        assert_eq!(sqlite_column("Vec<u8>").map(|c| c.sql_type), Some("BLOB"));
+        assert_eq!(sqlite_column("Vec<String>"), None);
+        assert_eq!(sqlite_column("Option<Option<u8>>"), None);
+    }
+
+    #[test]
+    fn test_sqlite_column_definition() {
+        assert_eq!(
+            sqlite_column("i64").unwrap().column_definition("id"),
+            "\"id\" INTEGER NOT NULL"
+        );
+        assert_eq!(
+            sqlite_column("Option<String>")
+                .unwrap()
+                .column_definition("name"),
+            "\"name\" TEXT"
+        );
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+    }
+
+    #[test]
+    fn test_sqlite_column_read_expr() {
+        let column = sqlite_column("Option<Box<str>>").unwrap();
+        assert_eq!(
+            column.read_expr(2).to_string(),
+            concat!(
+                "row . get :: < _ , Option < String > > (2usize) . map_err (sqlite_error) ?",
+                " . map (Into :: into)"
+            )
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use serde::Deserialize;
+use truc::record::type_resolver::TypeResolver;
+
+use super::{sqlite_column, SqliteColumn};
+use crate::{prelude::*, trace_filter};
+
+const READ_SQLITE_TRACE_NAME: &str = "read_sqlite";
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReadSqliteParams<'a> {
+    path: &'a str,
+    query: &'a str,
+    fields: TypedFieldsParam<'a>,
+    order_fields: Option<DirectedFieldsParam<'a>>,
+    distinct_fields: Option<FieldsParam<'a>>,
+    channel_capacity: Option<usize>,
+}
+
+#[derive(Getters)]
+pub struct ReadSqlite {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; 0],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 1],
+    path: String,
+    query: String,
+    fields: Vec<(ValidFieldName, SqliteColumn)>,
+    channel_capacity: Option<usize>,
+}
+
+impl ReadSqlite {
+    fn new<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; 0],
+        params: ReadSqliteParams,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        let valid_fields = params
+            .fields
+            .validate_new(|| trace_filter!(trace, READ_SQLITE_TRACE_NAME))?;
+
+        let columns = valid_fields
+            .iter()
+            .map(|(name, r#type)| {
+                sqlite_column(r#type.type_name())
+                    .map(|column| (name.clone(), column))
+                    .ok_or_else(|| ChainError::InvalidFieldType {
+                        type_name: r#type.type_name().to_owned(),
+                        trace: trace_filter!(trace, READ_SQLITE_TRACE_NAME),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The order and distinct facts cannot be verified against the query, they are trusted
+        // as declared.
+        let valid_order_fields = params
+            .order_fields
+            .map(|order_fields| {
+                order_fields.validate(
+                    |name| valid_fields.iter().any(|vf| vf.0.name() == name),
+                    || trace_filter!(trace, READ_SQLITE_TRACE_NAME),
+                )
+            })
+            .transpose()?;
+
+        let valid_distinct_fields = params
+            .distinct_fields
+            .map(|distinct_fields| {
+                distinct_fields.validate(
+                    |name| valid_fields.iter().any(|vf| vf.0.name() == name.name()),
+                    || trace_filter!(trace, READ_SQLITE_TRACE_NAME),
+                )
+            })
+            .transpose()?;
+
+        let mut streams = StreamsBuilder::new(&name, &inputs);
+        streams.new_main_stream(graph);
+
+        streams
+            .new_main_output(graph)
+            .update(|output_stream, facts_proof| {
+                {
+                    let mut output_stream_def = output_stream.record_definition().borrow_mut();
+                    for (name, r#type) in valid_fields.iter() {
+                        output_stream_def.add_dynamic_datum(name.name(), r#type.type_name());
+                    }
+                }
+                if let Some(order_fields) = valid_order_fields.as_ref() {
+                    output_stream.set_order_fact(
+                        order_fields
+                            .iter()
+                            .map(|field| field.as_ref().map(ValidFieldName::name)),
+                    );
+                }
+                if let Some(distinct_fields) = valid_distinct_fields.as_ref() {
+                    output_stream
+                        .set_distinct_fact(distinct_fields.iter().map(ValidFieldName::name));
+                }
+                Ok(facts_proof.order_facts_updated().distinct_facts_updated())
+            })?;
+
+        let outputs = streams.build();
+
+        Ok(Self {
+            name,
+            inputs,
+            outputs,
+            path: params.path.to_owned(),
+            query: params.query.to_owned(),
+            fields: columns,
+            channel_capacity: params.channel_capacity,
+        })
+    }
+}
+
+impl DynNode for ReadSqlite {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        chain.set_outputs_channel_capacity(&self.outputs, self.channel_capacity);
+
+        let thread_id = chain.new_threaded_source(
+            &self.name,
+            ChainThreadType::Regular,
+            &self.inputs,
+            &self.outputs,
+        );
+
+        let def = chain.stream_definition_fragments(self.outputs.single());
+        let record = def.record();
+        let unpacked_record = def.unpacked_record();
+
+        let error_type = graph.chain_customizer().error_type.to_name();
+
+        let name = self.name.to_string();
+        let path = &self.path;
+        let query = &self.query;
+
+        let fields = self.fields.iter().enumerate().map(|(index, (name, column))| {
+            let name = name.ident();
+            let read = column.read_expr(index);
+            quote! { #name: #read }
+        });
+
+        let thread_body = quote! {
+            let output = thread_control.output_0.take().expect("output 0");
+            let path = thread_control.chain_configuration.path(#name, #path);
+            move || {
+                let sqlite_error = |err: rusqlite::Error| #error_type::custom(err.to_string());
+
+                let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+                let mut statement = connection.prepare(#query).map_err(sqlite_error)?;
+                let mut rows = statement.query([]).map_err(sqlite_error)?;
+                while let Some(row) = rows.next().map_err(sqlite_error)? {
+                    let record = #record::new(#unpacked_record { #(#fields),* });
+                    output.send(Some(record))?;
+                }
+                output.send(None)?;
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread_id, &thread_body);
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn read_sqlite<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 0],
+    params: ReadSqliteParams,
+    trace: Trace,
+) -> ChainResult<ReadSqlite> {
+    ReadSqlite::new(graph, name, inputs, params, trace)
+}
@@ -0,0 +1,246 @@
+use itertools::Itertools;
+use serde::Deserialize;
+use truc::record::type_resolver::TypeResolver;
+
+use super::{quote_identifier, sqlite_column};
+use crate::{prelude::*, trace_filter};
+
+const WRITE_SQLITE_TRACE_NAME: &str = "write_sqlite";
+
+const DEFAULT_BATCH_SIZE: usize = 10000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Debug)]
+pub enum WriteSqliteMode {
+    /// Creates the table, fails if it already exists.
+    Create,
+    /// Creates the table if it does not exist, then appends the records.
+    Append,
+    /// Drops the table if it exists, then creates it.
+    Replace,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WriteSqliteParams<'a> {
+    path: &'a str,
+    table: &'a str,
+    mode: WriteSqliteMode,
+    batch_size: Option<usize>,
+    index: Option<bool>,
+}
+
+#[derive(Getters)]
+pub struct WriteSqlite {
+    name: FullyQualifiedName,
+    #[getset(get = "pub")]
+    inputs: [NodeStream; 1],
+    #[getset(get = "pub")]
+    outputs: [NodeStream; 0],
+    path: String,
+    fields: Vec<ValidFieldName>,
+    setup_sql: String,
+    insert_sql: String,
+    index_sql: Option<String>,
+    batch_size: usize,
+}
+
+impl WriteSqlite {
+    fn new<R: TypeResolver + Copy>(
+        graph: &mut GraphBuilder<R>,
+        name: FullyQualifiedName,
+        inputs: [NodeStream; 1],
+        params: WriteSqliteParams,
+        trace: Trace,
+    ) -> ChainResult<Self> {
+        let table = quote_identifier(params.table);
+
+        let (fields, column_definitions, index_columns) = {
+            let def = graph
+                .get_stream(inputs[0].record_type())
+                .unwrap_or_else(|| panic!(r#"stream "{}""#, inputs[0].record_type()))
+                .borrow();
+            let mut fields = Vec::new();
+            let mut column_definitions = Vec::new();
+            for d in def.get_current_data() {
+                let datum = def.get_datum_definition(d).expect("datum");
+                let column =
+                    sqlite_column(datum.type_name()).ok_or_else(|| ChainError::InvalidFieldType {
+                        type_name: datum.type_name().to_owned(),
+                        trace: trace_filter!(trace, WRITE_SQLITE_TRACE_NAME),
+                    })?;
+                column_definitions.push(column.column_definition(datum.name()));
+                fields.push(ValidFieldName::try_from(datum.name()).map_err(|_| {
+                    ChainError::InvalidFieldName {
+                        name: datum.name().to_owned(),
+                        trace: trace_filter!(trace, WRITE_SQLITE_TRACE_NAME),
+                    }
+                })?);
+            }
+            let index_columns = inputs[0]
+                .facts()
+                .order()
+                .iter()
+                .map(|directed| {
+                    let datum = def.get_datum_definition(**directed).expect("datum");
+                    let column = quote_identifier(datum.name());
+                    if directed.is_asc() {
+                        format!("{} ASC", column)
+                    } else {
+                        format!("{} DESC", column)
+                    }
+                })
+                .collect::<Vec<_>>();
+            (fields, column_definitions, index_columns)
+        };
+
+        let create_table = format!(
+            "CREATE TABLE {}{} ({})",
+            if params.mode == WriteSqliteMode::Append {
+                "IF NOT EXISTS "
+            } else {
+                ""
+            },
+            table,
+            column_definitions.join(", ")
+        );
+        let setup_sql = if params.mode == WriteSqliteMode::Replace {
+            format!("DROP TABLE IF EXISTS {}; {};", table, create_table)
+        } else {
+            format!("{};", create_table)
+        };
+
+        let insert_sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            fields
+                .iter()
+                .map(|field| quote_identifier(field.name()))
+                .join(", "),
+            (1..=fields.len()).map(|i| format!("?{}", i)).join(", ")
+        );
+
+        if params.index == Some(true) && index_columns.is_empty() {
+            return Err(ChainError::Other {
+                msg: "index requires the input to have an order fact".to_owned(),
+                trace: trace_filter!(trace, WRITE_SQLITE_TRACE_NAME),
+            });
+        }
+        let index_sql = params.index.unwrap_or(false).then(|| {
+            format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} ({});",
+                quote_identifier(&format!("{}_order", params.table)),
+                table,
+                index_columns.join(", ")
+            )
+        });
+
+        let batch_size = params.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+        if batch_size == 0 {
+            return Err(ChainError::Other {
+                msg: "batch_size must be greater than 0".to_owned(),
+                trace: trace_filter!(trace, WRITE_SQLITE_TRACE_NAME),
+            });
+        }
+
+        Ok(Self {
+            name,
+            inputs,
+            outputs: [],
+            path: params.path.to_owned(),
+            fields,
+            setup_sql,
+            insert_sql,
+            index_sql,
+            batch_size,
+        })
+    }
+}
+
+impl DynNode for WriteSqlite {
+    fn name(&self) -> &FullyQualifiedName {
+        &self.name
+    }
+
+    fn inputs(&self) -> &[NodeStream] {
+        &self.inputs
+    }
+
+    fn outputs(&self) -> &[NodeStream] {
+        &self.outputs
+    }
+
+    fn gen_chain(&self, graph: &Graph, chain: &mut Chain) {
+        let thread = chain.get_thread_by_source(&self.inputs[0], &self.name, self.outputs.none());
+
+        let input = thread.format_input(self.inputs[0].source(), graph.chain_customizer(), true);
+
+        let error_type = graph.chain_customizer().error_type.to_name();
+
+        let name = self.name.to_string();
+        let path = &self.path;
+        let setup_sql = &self.setup_sql;
+        let insert_sql = &self.insert_sql;
+        let batch_size = self.batch_size;
+        let create_index = self.index_sql.as_ref().map(|index_sql| {
+            quote! {
+                connection.execute_batch(#index_sql).map_err(sqlite_error)?;
+            }
+        });
+        let fields = self.fields.iter().map(ValidFieldName::ident);
+
+        let thread_body = quote! {
+            #input
+
+            let path = thread_control.chain_configuration.path(#name, #path);
+            move || {
+                use fallible_iterator::FallibleIterator;
+
+                let sqlite_error = |err: rusqlite::Error| #error_type::custom(err.to_string());
+
+                let mut connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+                connection.execute_batch(#setup_sql).map_err(sqlite_error)?;
+
+                let mut done = false;
+                while !done {
+                    let transaction = connection.transaction().map_err(sqlite_error)?;
+                    {
+                        let mut statement = transaction.prepare_cached(#insert_sql).map_err(sqlite_error)?;
+                        for _ in 0..#batch_size {
+                            if let Some(record) = input.next()? {
+                                statement
+                                    .execute(rusqlite::params![#(record.#fields()),*])
+                                    .map_err(sqlite_error)?;
+                            } else {
+                                done = true;
+                                break;
+                            }
+                        }
+                    }
+                    transaction.commit().map_err(sqlite_error)?;
+                }
+
+                #create_index
+
+                Ok(())
+            }
+        };
+
+        chain.implement_node_thread(self, thread.thread_id, &thread_body);
+
+        chain.set_thread_main(thread.thread_id, self.name.clone());
+    }
+
+    fn all_nodes(&self) -> Box<dyn Iterator<Item = &dyn DynNode> + '_> {
+        Box::new(Some(self as &dyn DynNode).into_iter())
+    }
+}
+
+pub fn write_sqlite<R: TypeResolver + Copy>(
+    graph: &mut GraphBuilder<R>,
+    name: FullyQualifiedName,
+    inputs: [NodeStream; 1],
+    params: WriteSqliteParams,
+    trace: Trace,
+) -> ChainResult<WriteSqlite> {
+    WriteSqlite::new(graph, name, inputs, params, trace)
+}
@@ -31,6 +31,12 @@ pub struct GraphBuilder<R: TypeResolver + Copy> {
     params: ParamsBuilder,
     #[new(default)]
     anchor_table_count: usize,
+    #[new(default)]
+    config_params: Vec<ChainConfigParam>,
+    #[new(default)]
+    auto_order: bool,
+    #[new(default)]
+    warnings: Vec<String>,
 }
 
 impl<R: TypeResolver + Copy> GraphBuilder<R> {
@@ -59,9 +65,62 @@ impl<R: TypeResolver + Copy> GraphBuilder<R> {
         self.record_definitions.get(record_type)
     }
 
+    /// Declares a typed configuration parameter of the chain.
+    pub fn add_config_param(&mut self, param: ChainConfigParam, trace: &Trace) -> ChainResult<()> {
+        let error = |msg: String| ChainError::Other {
+            msg,
+            trace: trace.to_owned(),
+        };
+        if syn::parse_str::<syn::Ident>(&param.name).is_err() {
+            return Err(error(format!(
+                r#"config parameter name "{}" is not a valid identifier"#,
+                param.name
+            )));
+        }
+        if syn::parse_str::<syn::Type>(&param.r#type).is_err() {
+            return Err(error(format!(
+                r#"config parameter "{}" has an invalid type "{}""#,
+                param.name, param.r#type
+            )));
+        }
+        if self.config_params.iter().any(|p| p.name == param.name) {
+            return Err(error(format!(
+                r#"config parameter "{}" is declared twice"#,
+                param.name
+            )));
+        }
+        self.config_params.push(param);
+        Ok(())
+    }
+
+    /// Enables the auto-order mode.
+    ///
+    /// In that mode, the filters expecting an ordered input sort it themselves when it is not,
+    /// instead of failing with [`ChainError::ExpectedMinimalOrder`], and a sort on an input
+    /// already in the requested order passes its records through. Inputs are never
+    /// deduplicated: a filter expecting a distinct input still fails with
+    /// [`ChainError::ExpectedDistinct`], since dropping the duplicated records would silently
+    /// change the result.
+    pub fn set_auto_order(&mut self, auto_order: bool) {
+        self.auto_order = auto_order;
+    }
+
+    pub fn auto_order(&self) -> bool {
+        self.auto_order
+    }
+
+    /// Reports something the user may want to change in the graph, without failing.
+    ///
+    /// The warnings are kept in the built graph, for the caller to print them.
+    pub fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
     pub fn build(self, entry_nodes: Vec<Box<dyn DynNode>>) -> Graph {
         Graph {
             chain_customizer: self.chain_customizer,
+            config_params: self.config_params,
+            warnings: self.warnings,
             record_definitions: self
                 .record_definitions
                 .into_iter()
@@ -0,0 +1,363 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+use itertools::Itertools;
+use serde::Serialize;
+use truc::record::definition::{DatumId, RecordDefinition, RecordVariantId};
+
+use crate::{chain::ChainPlan, prelude::*};
+
+/// What a graph turns into, as returned by [`Graph::explain`].
+#[derive(Serialize, Debug)]
+pub struct GraphExplanation {
+    pub nodes: Vec<NodeExplanation>,
+    pub threads: Vec<ThreadExplanation>,
+    pub pipes: Vec<PipeExplanation>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NodeExplanation {
+    pub name: String,
+    /// The thread the node is implemented in.
+    pub thread: Option<usize>,
+    pub inputs: Vec<StreamExplanation>,
+    pub outputs: Vec<StreamExplanation>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StreamExplanation {
+    /// The node the records are read from.
+    pub source: String,
+    #[serde(flatten)]
+    pub record: RecordExplanation,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SubStreamExplanation {
+    /// The field of the parent record holding the records of the sub-stream.
+    pub field: String,
+    #[serde(flatten)]
+    pub record: RecordExplanation,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RecordExplanation {
+    pub record_type: String,
+    pub variant: String,
+    pub fields: Vec<FieldExplanation>,
+    pub order: Vec<String>,
+    pub distinct: Vec<String>,
+    pub sub_streams: Vec<SubStreamExplanation>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FieldExplanation {
+    pub name: String,
+    pub r#type: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ThreadExplanation {
+    pub id: usize,
+    pub name: String,
+    pub thread_type: String,
+    pub main: Option<String>,
+    pub nodes: Vec<String>,
+    pub input_pipes: Vec<usize>,
+    pub output_pipes: Vec<usize>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PipeExplanation {
+    pub id: usize,
+    pub source: String,
+}
+
+impl GraphExplanation {
+    pub(crate) fn new(graph: &Graph, plan: ChainPlan) -> Self {
+        let nodes = graph
+            .entry_nodes
+            .iter()
+            .flat_map(|entry_node| entry_node.all_nodes())
+            .map(|node| NodeExplanation {
+                name: node.name().to_string(),
+                thread: plan
+                    .threads
+                    .iter()
+                    .find(|thread| thread.nodes.contains(node.name()))
+                    .map(|thread| thread.id),
+                inputs: node
+                    .inputs()
+                    .iter()
+                    .map(|input| StreamExplanation::new(graph, input))
+                    .collect(),
+                outputs: node
+                    .outputs()
+                    .iter()
+                    .map(|output| StreamExplanation::new(graph, output))
+                    .collect(),
+            })
+            .collect();
+        let threads = plan
+            .threads
+            .iter()
+            .map(|thread| ThreadExplanation {
+                id: thread.id,
+                name: thread.name.to_string(),
+                thread_type: format!("{:?}", thread.thread_type),
+                main: thread.main.as_ref().map(ToString::to_string),
+                nodes: thread.nodes.iter().map(ToString::to_string).collect(),
+                input_pipes: thread.input_pipes.clone(),
+                output_pipes: thread.output_pipes.clone(),
+            })
+            .collect();
+        let pipes = plan
+            .pipes
+            .iter()
+            .map(|(id, source)| PipeExplanation {
+                id: *id,
+                source: source.to_string(),
+            })
+            .collect();
+        Self {
+            nodes,
+            threads,
+            pipes,
+            warnings: graph.warnings().to_vec(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("json")
+    }
+}
+
+impl StreamExplanation {
+    fn new(graph: &Graph, stream: &NodeStream) -> Self {
+        Self {
+            source: stream.source().to_string(),
+            record: RecordExplanation::new(
+                graph,
+                stream.record_type(),
+                stream.variant_id(),
+                stream.facts(),
+                stream.sub_streams(),
+            ),
+        }
+    }
+}
+
+impl RecordExplanation {
+    fn new(
+        graph: &Graph,
+        record_type: &StreamRecordType,
+        variant_id: RecordVariantId,
+        facts: &StreamFacts,
+        sub_streams: &BTreeMap<DatumId, NodeSubStream>,
+    ) -> Self {
+        let def: &RecordDefinition = &graph.record_definitions()[record_type];
+        Self {
+            record_type: record_type.to_string(),
+            variant: variant_id.to_string(),
+            fields: def[variant_id]
+                .data()
+                .map(|d| FieldExplanation {
+                    name: def[d].name().to_owned(),
+                    r#type: def[d].type_name().to_owned(),
+                })
+                .collect(),
+            order: facts
+                .order()
+                .iter()
+                .map(|d| d.as_ref().map(|d| def[*d].name()).to_string())
+                .collect(),
+            distinct: facts
+                .distinct()
+                .iter()
+                .map(|d| def[*d].name().to_owned())
+                .collect(),
+            sub_streams: sub_streams
+                .iter()
+                .map(|(d, sub_stream)| SubStreamExplanation {
+                    field: def[*d].name().to_owned(),
+                    record: RecordExplanation::new(
+                        graph,
+                        sub_stream.record_type(),
+                        sub_stream.variant_id(),
+                        sub_stream.facts(),
+                        sub_stream.sub_streams(),
+                    ),
+                })
+                .collect(),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut Formatter<'_>, indent: usize) -> std::fmt::Result {
+        let pad = "    ".repeat(indent);
+        writeln!(
+            f,
+            "{}fields: {}",
+            pad,
+            self.fields
+                .iter()
+                .map(|field| format!("{}: {}", field.name, field.r#type))
+                .join(", ")
+        )?;
+        writeln!(f, "{}order: [{}]", pad, self.order.iter().join(", "))?;
+        writeln!(f, "{}distinct: [{}]", pad, self.distinct.iter().join(", "))?;
+        for sub_stream in &self.sub_streams {
+            writeln!(
+                f,
+                "{}sub-stream {}: {} variant {}",
+                pad, sub_stream.field, sub_stream.record.record_type, sub_stream.record.variant
+            )?;
+            sub_stream.record.fmt_indented(f, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for GraphExplanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for node in &self.nodes {
+            write!(f, "node {}", node.name)?;
+            if let Some(thread) = node.thread {
+                write!(f, " (thread {})", thread)?;
+            }
+            writeln!(f)?;
+            for (kind, streams) in [("input", &node.inputs), ("output", &node.outputs)] {
+                for (index, stream) in streams.iter().enumerate() {
+                    writeln!(
+                        f,
+                        "    {} {} from {}: {} variant {}",
+                        kind,
+                        index,
+                        stream.source,
+                        stream.record.record_type,
+                        stream.record.variant
+                    )?;
+                    stream.record.fmt_indented(f, 2)?;
+                }
+            }
+        }
+        for thread in &self.threads {
+            writeln!(
+                f,
+                "thread {} {} ({}): main {}, nodes [{}], input pipes [{}], output pipes [{}]",
+                thread.id,
+                thread.name,
+                thread.thread_type,
+                thread.main.as_deref().unwrap_or("none"),
+                thread.nodes.iter().join(", "),
+                thread.input_pipes.iter().join(", "),
+                thread.output_pipes.iter().join(", "),
+            )?;
+        }
+        for pipe in &self.pipes {
+            writeln!(f, "pipe {}: {}", pipe.id, pipe.source)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use truc::record::type_resolver::{StaticTypeResolver, TypeResolver};
+
+    use crate::{
+        filter::{
+            function::{produce::function_produce, terminate::function_terminate},
+            sort::sort,
+        },
+        prelude::*,
+    };
+
+    #[test]
+    fn should_explain_graph() {
+        let type_resolver = {
+            let mut resolver = StaticTypeResolver::new();
+            resolver.add_std_types();
+            resolver
+        };
+        let mut graph = GraphBuilder::new(&type_resolver, ChainCustomizer::default());
+        let name = FullyQualifiedName::new("main");
+
+        let params = graph
+            .params()
+            .from_ron_str(
+                r##"(
+                    fields: [("num", "u8"), ("label", "String")],
+                    body: r#"{ output.send(None)?; Ok(()) }"#,
+                )"##,
+            )
+            .unwrap();
+        let produce =
+            function_produce(&mut graph, name.sub("produce"), [], params, Trace::root()).unwrap();
+        let params = graph
+            .params()
+            .from_ron_str(r#"(fields: ["num", Descending("label")])"#)
+            .unwrap();
+        let sort = sort(
+            &mut graph,
+            name.sub("sort"),
+            produce.outputs().clone(),
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+        let params = graph.params().from_ron_str(r#"(body: "Ok(())")"#).unwrap();
+        let terminate = function_terminate(
+            &mut graph,
+            name.sub("terminate"),
+            sort.outputs().clone(),
+            params,
+            Trace::root(),
+        )
+        .unwrap();
+
+        let graph = graph.build(vec![Box::new(produce), Box::new(sort), Box::new(terminate)]);
+        let explanation = graph.explain();
+
+        assert_eq!(
+            vec!["main::produce", "main::sort", "main::terminate"],
+            explanation
+                .nodes
+                .iter()
+                .map(|node| node.name.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(explanation.nodes.iter().all(|node| node.thread.is_some()));
+        let sorted = &explanation.nodes[1].outputs[0];
+        assert_eq!("main::sort", sorted.source);
+        assert_eq!("main::produce", sorted.record.record_type);
+        assert_eq!(
+            vec![("num", "u8"), ("label", "String")],
+            sorted
+                .record
+                .fields
+                .iter()
+                .map(|field| (field.name.as_str(), field.r#type.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec!["asc(num)", "desc(label)"], sorted.record.order);
+
+        let text = explanation.to_string();
+        assert!(text.contains("order: [asc(num), desc(label)]"), "{}", text);
+
+        let json = serde_json::from_str::<serde_json::Value>(&explanation.to_json()).unwrap();
+        assert_eq!(
+            serde_json::json!("desc(label)"),
+            json["nodes"][1]["outputs"][0]["order"][1]
+        );
+        assert_eq!(
+            serde_json::json!("u8"),
+            json["nodes"][1]["outputs"][0]["fields"][0]["type"]
+        );
+    }
+}
@@ -11,7 +11,9 @@ use truc::{
     record::definition::{DatumId, RecordDefinition},
 };
 
+use self::explain::GraphExplanation;
 use crate::{
+    chain::ChainPlan,
     drawing::{
         format_svg,
         graph::{RecordsDrawingHelper, StreamsDrawingHelper},
@@ -22,10 +24,13 @@ use crate::{
 
 pub mod builder;
 pub mod error;
+pub mod explain;
 pub mod node;
 
 pub struct Graph {
     chain_customizer: ChainCustomizer,
+    config_params: Vec<ChainConfigParam>,
+    warnings: Vec<String>,
     record_definitions: BTreeMap<StreamRecordType, RecordDefinition>,
     entry_nodes: Vec<Box<dyn DynNode>>,
 }
@@ -39,6 +44,11 @@ impl Graph {
         &self.record_definitions
     }
 
+    /// The warnings reported while building the graph.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
     pub fn generate(&self, output: &Path) -> Result<(), std::io::Error> {
         use std::io::Write;
 
@@ -65,33 +75,55 @@ impl Graph {
 
         {
             let mut scope = Scope::new();
-            scope.import("fallible_iterator", "FallibleIterator");
-            scope.import(
-                &self.chain_customizer.error_type_path(),
-                &self.chain_customizer.error_type_name(),
-            );
-            for (path, ty) in &self.chain_customizer.custom_module_imports {
-                scope.import(path, ty);
-            }
-
-            scope.raw("mod streams;");
-
-            let mut chain = Chain::new(&self.chain_customizer, &mut scope);
-
-            for node in &self.entry_nodes {
-                node.gen_chain(self, &mut chain);
-            }
-
-            chain.gen_chain();
+            let plan = self.gen_chain(&mut scope);
 
             let mut file = File::create(output.join("chain.rs")).unwrap();
             write!(file, "{}", scope.to_string()).unwrap();
+
+            let mut file = File::create(output.join("chain_plan.txt")).unwrap();
+            write!(file, "{}", plan).unwrap();
         }
         rustfmt_generated_file(output.join("chain.rs").as_path());
 
         Ok(())
     }
 
+    pub(crate) fn gen_chain(&self, scope: &mut Scope) -> ChainPlan {
+        scope.import("fallible_iterator", "FallibleIterator");
+        scope.import(
+            &self.chain_customizer.error_type_path(),
+            &self.chain_customizer.error_type_name(),
+        );
+        for (path, ty) in &self.chain_customizer.custom_module_imports {
+            scope.import(path, ty);
+        }
+
+        scope.raw("mod streams;");
+
+        let mut chain = Chain::new(
+            &self.chain_customizer,
+            scope,
+            &self.config_params,
+            &self.record_definitions,
+        );
+
+        for node in &self.entry_nodes {
+            node.gen_chain(self, &mut chain);
+        }
+
+        chain.gen_chain()
+    }
+
+    /// Explains what the graph turns into without generating anything: the nodes with their
+    /// streams, records and facts, and the threads and pipes the chain is planned into.
+    ///
+    /// The explanation renders as text with `Display`, or as JSON.
+    pub fn explain(&self) -> GraphExplanation {
+        let mut scope = Scope::new();
+        let plan = self.gen_chain(&mut scope);
+        GraphExplanation::new(self, plan)
+    }
+
     fn streams_to_abstract_drawing(&self) -> Drawing {
         let mut helper = StreamsDrawingHelper::default();
 
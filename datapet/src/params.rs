@@ -211,6 +211,12 @@ impl<'a> FieldsParam<'a> {
 #[derive(Deserialize, Debug, Deref)]
 pub struct DirectedFieldsParam<'a>(#[serde(borrow)] Box<[Directed<&'a str>]>);
 
+impl<'a> From<Vec<Directed<&'a str>>> for DirectedFieldsParam<'a> {
+    fn from(fields: Vec<Directed<&'a str>>) -> Self {
+        Self(fields.into_boxed_slice())
+    }
+}
+
 impl<'a> DirectedFieldsParam<'a> {
     pub fn validate<L, TRACE>(
         self,
@@ -1,7 +1,7 @@
 pub use crate::{
     chain::{
-        error::ChainError, Chain, ChainCustomizer, ChainResult, ChainThreadType, ImportScope,
-        Trace, TraceElement,
+        error::ChainError, Chain, ChainConfigParam, ChainCustomizer, ChainFactsCheck, ChainResult,
+        ChainRuntime, ChainThreadType, ImportScope, Trace, TraceElement,
     },
     graph::{
         builder::{
@@ -0,0 +1,28 @@
+use datapet_support::data::buffer::BufferCompression;
+use proc_macro2::TokenStream;
+
+use crate::prelude::*;
+
+/// Generates the options of the buffers the node `name` spills its records to, every filter
+/// building a buffer going through it.
+///
+/// The spill directory, and the compression overriding the one of the filter if any, come from
+/// the `chain_configuration` in scope, which inline nodes have.
+pub fn buffer_options(name: &FullyQualifiedName, compression: BufferCompression) -> TokenStream {
+    let compression = match compression {
+        BufferCompression::None => quote! { None },
+        BufferCompression::Lz4 => quote! { Lz4 },
+        BufferCompression::Zstd => quote! { Zstd },
+    };
+    let name = name.to_string();
+    quote! {
+        datapet_support::data::buffer::BufferOptions {
+            spill_dir: chain_configuration.spill_dir.clone(),
+            compression: chain_configuration.spill_compression(
+                #name,
+                datapet_support::data::buffer::BufferCompression::#compression,
+            ),
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use datapet_support::iterator::io::compression::Compression;
+use proc_macro2::TokenStream;
+
+/// Generates the compression given to `compression::open` and `compression::create`, which
+/// detect it from the file when not specified.
+pub fn file_compression(compression: Option<Compression>) -> TokenStream {
+    let compression = match compression {
+        None => return quote! { None },
+        Some(Compression::None) => quote! { None },
+        Some(Compression::Gzip) => quote! { Gzip },
+        Some(Compression::Zstd) => quote! { Zstd },
+    };
+    quote! {
+        Some(datapet_support::iterator::io::compression::Compression::#compression)
+    }
+}
@@ -1,4 +1,6 @@
+pub mod buffer;
 pub mod cmp;
+pub mod compression;
 pub mod eq;
 pub mod name;
 pub mod valid;
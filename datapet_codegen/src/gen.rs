@@ -5,8 +5,8 @@ use std::{
 
 use datapet_lang::{
     ast::{
-        ConnectedFilter, Graph, GraphDefinition, Module, ModuleItem, StreamLine, StreamLineInput,
-        StreamLineOutput, UseDeclaration,
+        ConfigParam, ConnectedFilter, Graph, GraphDefinition, Module, ModuleItem, StreamLine,
+        StreamLineInput, StreamLineOutput, UseDeclaration,
     },
     location::Location,
     parser::parse_module,
@@ -222,6 +222,33 @@ fn dtpt_graph<'a>(
     let feature_gate = annotations
         .feature
         .map(|feature| quote![#[cfg(feature = #feature)]]);
+    let config_params = annotations.config.iter().map(|param| {
+        let ConfigParam {
+            name,
+            r#type: ty,
+            default,
+            doc,
+        } = param;
+        let default = match default {
+            Some(default) => quote! { Some(#default.to_owned()) },
+            None => quote! { None },
+        };
+        let doc = match doc {
+            Some(doc) => quote! { Some(#doc.to_owned()) },
+            None => quote! { None },
+        };
+        quote! {
+            graph.add_config_param(
+                ChainConfigParam {
+                    name: #name.to_owned(),
+                    r#type: #ty.to_owned(),
+                    default: #default,
+                    doc: #doc,
+                },
+                &trace,
+            )?;
+        }
+    });
     quote! {
         #feature_gate
         pub fn #name<R: TypeResolver + Copy>(
@@ -232,6 +259,8 @@ fn dtpt_graph<'a>(
             let handlebars = Handlebars::new();
             let handlebars_data = BTreeMap::<&str, &str>::new();
 
+            #(#config_params)*
+
             #body
 
             Ok(NodeCluster::new(
@@ -621,6 +650,10 @@ pub(crate) fn generate_module<'a>(
             {
                 let graph = dtpt_main(new_graph_builder())?;
 
+                for warning in graph.warnings() {
+                    println!("cargo:warning={}", warning);
+                }
+
                 graph.generate(out_dir)?;
 
                 Ok(())
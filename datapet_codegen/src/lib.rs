@@ -101,6 +101,10 @@ impl ModuleCode {
             quote! {
                 let graph = dtpt_main(new_graph_builder(&[#(#path),*]))?;
 
+                for warning in graph.warnings() {
+                    println!("cargo:warning={}", warning);
+                }
+
                 graph.generate(out_dir)?;
             }
         });
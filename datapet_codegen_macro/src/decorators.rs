@@ -12,9 +12,13 @@ pub fn tracking_allocator_static(_input: proc_macro::TokenStream) -> proc_macro:
 
         #[cfg(feature = #MONITOR_OPTION)]
         mod dtpt_monitor {
-            use std::sync::Mutex;
+            use std::sync::{
+                atomic::{AtomicBool, AtomicIsize, Ordering},
+                Mutex,
+            };
 
-            use tracking_allocator::{AllocationGroupId, AllocationTracker};
+            use fallible_iterator::FallibleIterator;
+            use tracking_allocator::{AllocationGroupId, AllocationGroupToken, AllocationTracker};
 
             pub struct MemoryTracker;
 
@@ -28,14 +32,85 @@ pub fn tracking_allocator_static(_input: proc_macro::TokenStream) -> proc_macro:
                 wrapped: 0,
             });
 
+            /// Groups with a greater id are only accounted in `DATA`.
+            pub const MAX_GROUPS: usize = 256;
+
+            static MAX_GROUPS_EXCEEDED: AtomicBool = AtomicBool::new(false);
+
+            #[allow(clippy::declare_interior_mutable_const)]
+            const GROUP_OBJECT_INIT: AtomicIsize = AtomicIsize::new(0);
+
+            /// The object size held by each allocation group, by group id.
+            static GROUP_OBJECT: [AtomicIsize; MAX_GROUPS] = [GROUP_OBJECT_INIT; MAX_GROUPS];
+
+            /// The allocation groups registered by the chain, by group id.
+            pub static GROUP_NAMES: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+
+            pub fn register_group(name: &str) -> AllocationGroupToken {
+                let token =
+                    AllocationGroupToken::register().expect("failed to register allocation group");
+                let id = token.id().as_usize().get();
+                if id >= MAX_GROUPS && !MAX_GROUPS_EXCEEDED.swap(true, Ordering::Relaxed) {
+                    eprintln!(
+                        "dtpt_monitor: more than {} allocation groups, the memory of {} and the \
+                         next nodes is only accounted in the totals",
+                        MAX_GROUPS, name,
+                    );
+                }
+                GROUP_NAMES.lock().unwrap().push((id, name.to_owned()));
+                token
+            }
+
+            /// The object size held by a group, `None` if it is not tracked.
+            pub fn group_object(id: usize) -> Option<isize> {
+                GROUP_OBJECT
+                    .get(id)
+                    .map(|object| object.load(Ordering::Relaxed))
+            }
+
+            /// Runs a thread main in the allocation group of its node.
+            pub fn run_in_group<T>(name: &str, thread_main: impl FnOnce() -> T) -> T {
+                let mut token = register_group(name);
+                let _guard = token.enter();
+                thread_main()
+            }
+
+            /// Produces the records of an inline node in its allocation group.
+            pub struct GroupIterator<I> {
+                iter: I,
+                token: AllocationGroupToken,
+            }
+
+            impl<I> GroupIterator<I> {
+                pub fn new(iter: I, name: &str) -> Self {
+                    Self {
+                        iter,
+                        token: register_group(name),
+                    }
+                }
+            }
+
+            impl<I: FallibleIterator> FallibleIterator for GroupIterator<I> {
+                type Item = I::Item;
+                type Error = I::Error;
+
+                fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+                    let _guard = self.token.enter();
+                    self.iter.next()
+                }
+            }
+
             impl AllocationTracker for MemoryTracker {
                 fn allocated(
                     &self,
                     _addr: usize,
                     object_size: usize,
                     wrapped_size: usize,
-                    _group_id: AllocationGroupId,
+                    group_id: AllocationGroupId,
                     ) {
+                    if let Some(object) = GROUP_OBJECT.get(group_id.as_usize().get()) {
+                        object.fetch_add(object_size as isize, Ordering::Relaxed);
+                    }
                     let mut data = DATA.lock().unwrap();
                     data.object += object_size;
                     data.wrapped += wrapped_size;
@@ -46,9 +121,13 @@ pub fn tracking_allocator_static(_input: proc_macro::TokenStream) -> proc_macro:
                     _addr: usize,
                     object_size: usize,
                     wrapped_size: usize,
-                    _source_group_id: AllocationGroupId,
+                    source_group_id: AllocationGroupId,
                     _current_group_id: AllocationGroupId,
                     ) {
+                    // The memory is attributed to the group which allocated it, whichever frees it
+                    if let Some(object) = GROUP_OBJECT.get(source_group_id.as_usize().get()) {
+                        object.fetch_sub(object_size as isize, Ordering::Relaxed);
+                    }
                     let mut data = DATA.lock().unwrap();
                     data.object -= object_size;
                     data.wrapped -= wrapped_size;
@@ -94,4 +94,17 @@ pub struct Graph<'a> {
 pub struct GraphAnnotations<'a> {
     pub name: Option<&'a str>,
     pub feature: Option<&'a str>,
+    #[serde(default, borrow)]
+    pub config: Vec<ConfigParam<'a>>,
+}
+
+/// A configuration parameter of the chain, read from its configuration variables when it starts.
+#[derive(PartialEq, Eq, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigParam<'a> {
+    pub name: &'a str,
+    pub r#type: &'a str,
+    /// The value used when the variable is not set, parsed like the variable would be.
+    pub default: Option<&'a str>,
+    pub doc: Option<&'a str>,
 }
@@ -0,0 +1,428 @@
+use std::{
+    any::Any,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{RecvError, SendError},
+        Arc, Condvar, Mutex,
+    },
+};
+
+use crate::iterator::sync::mpsc::{ChannelReceiver, ChannelSender, RecordReceiver};
+
+/// The way a chain thread failed.
+#[derive(Debug)]
+pub enum ThreadFailureCause<E> {
+    Error(E),
+    /// The thread panicked, with the panic payload if it is a string.
+    Panic(String),
+}
+
+/// A chain thread which failed, either by returning an error or by panicking.
+#[derive(Debug)]
+pub struct ThreadFailure<E> {
+    pub thread_id: usize,
+    /// The name of the filter run by the thread.
+    pub name: &'static str,
+    pub cause: ThreadFailureCause<E>,
+}
+
+/// The error of a chain interrupted from outside, e.g. by a signal.
+#[derive(Error, Clone, Copy, PartialEq, Eq, Debug)]
+#[error("Chain interrupted")]
+pub struct Interrupted;
+
+impl<E> ThreadFailure<E> {
+    /// The error returned by the thread, or the error built by `panicked` from a message naming
+    /// the thread which panicked.
+    pub fn into_error<P>(self, panicked: P) -> E
+    where
+        P: FnOnce(String) -> E,
+    {
+        match self.cause {
+            ThreadFailureCause::Error(err) => err,
+            ThreadFailureCause::Panic(message) => panicked(format!(
+                "Thread {} ({}) panicked: {}",
+                self.thread_id, self.name, message
+            )),
+        }
+    }
+}
+
+struct RecordedFailure<E> {
+    failure: ThreadFailure<E>,
+    /// Whether the thread stopped on a closed pipe, which makes the failure a consequence of
+    /// another one.
+    pipe: bool,
+}
+
+/// A cancellation token shared by the threads of a chain.
+///
+/// The first thread failure cancels the chain: background threads are interrupted and the pipes
+/// between threads report being closed, see [`CancellableReceiver`], so that the other threads
+/// stop. Failures of threads which stopped on a closed pipe are only kept if no thread failed for
+/// another reason, so that the reported error is the originating one.
+pub struct Cancellation<E> {
+    cancelled: AtomicBool,
+    interrupted: AtomicBool,
+    failure: Mutex<Option<RecordedFailure<E>>>,
+    pipe_failures: Mutex<Vec<usize>>,
+    interrupts: Mutex<Vec<Arc<(Mutex<bool>, Condvar)>>>,
+}
+
+impl<E> Cancellation<E> {
+    pub fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            interrupted: AtomicBool::new(false),
+            failure: Mutex::new(None),
+            pipe_failures: Mutex::new(Vec::new()),
+            interrupts: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Acquire)
+    }
+
+    /// Registers the interrupt of a background thread, notified on cancellation.
+    pub fn add_interrupt(&self, interrupt: Arc<(Mutex<bool>, Condvar)>) {
+        self.interrupts
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(interrupt);
+    }
+
+    /// Records that a pipe of a thread was found closed, so that the failure of the thread, if
+    /// any, is a consequence of another one.
+    pub fn pipe_failed(&self, thread_id: usize) {
+        let mut pipe_failures = self
+            .pipe_failures
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if !pipe_failures.contains(&thread_id) {
+            pipe_failures.push(thread_id);
+        }
+    }
+
+    /// Runs the body of a thread, recording its failure, if any.
+    pub fn run<F>(&self, thread_id: usize, name: &'static str, f: F)
+    where
+        F: FnOnce() -> Result<(), E>,
+    {
+        let cause = match catch_unwind(AssertUnwindSafe(f)) {
+            Ok(Ok(())) => return,
+            Ok(Err(err)) => ThreadFailureCause::Error(err),
+            Err(payload) => ThreadFailureCause::Panic(panic_message(payload)),
+        };
+        self.fail(ThreadFailure {
+            thread_id,
+            name,
+            cause,
+        });
+    }
+
+    /// Records a thread failure and cancels the chain.
+    pub fn fail(&self, failure: ThreadFailure<E>) {
+        let pipe = matches!(failure.cause, ThreadFailureCause::Error(_))
+            && self
+                .pipe_failures
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .contains(&failure.thread_id);
+        {
+            let mut current = self.failure.lock().unwrap_or_else(|err| err.into_inner());
+            let replace = match current.as_ref() {
+                None => true,
+                Some(current) => current.pipe && !pipe,
+            };
+            if replace {
+                *current = Some(RecordedFailure { failure, pipe });
+            }
+        }
+        self.cancel();
+    }
+
+    /// Cancels the chain from outside, without any thread failure.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Release);
+        self.cancel();
+    }
+
+    fn cancel(&self) {
+        if !self.cancelled.swap(true, Ordering::AcqRel) {
+            for interrupt in self
+                .interrupts
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .iter()
+            {
+                let mut is_interrupted = interrupt.0.lock().unwrap_or_else(|err| err.into_inner());
+                *is_interrupted = true;
+                interrupt.1.notify_all();
+            }
+        }
+    }
+
+    /// Takes the recorded failure, if any, unless the chain was interrupted and the failure is a
+    /// consequence of it.
+    pub fn take_failure(&self) -> Option<ThreadFailure<E>> {
+        self.failure
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+            .filter(|recorded| !(self.is_interrupted() && recorded.pipe))
+            .map(|recorded| recorded.failure)
+    }
+
+    /// Takes the recorded failure, if any, as the error of the failed thread, see
+    /// [`ThreadFailure::into_error`].
+    pub fn result<P>(&self, panicked: P) -> Result<(), E>
+    where
+        P: FnOnce(String) -> E,
+    {
+        self.take_failure()
+            .map_or(Ok(()), |failure| Err(failure.into_error(panicked)))
+    }
+
+    /// Takes the recorded failure, if any, as the error of the failed thread, unless the chain
+    /// was interrupted and the failure is a consequence of it, in which case [`Interrupted`] is
+    /// returned.
+    pub fn result_or_interrupted<P>(&self, panicked: P) -> Result<(), E>
+    where
+        E: From<Interrupted>,
+        P: FnOnce(String) -> E,
+    {
+        match self.take_failure() {
+            Some(failure) => Err(failure.into_error(panicked)),
+            None if self.is_interrupted() => Err(Interrupted.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E> Default for Cancellation<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The receiving half of a pipe, which reports being closed once the chain is cancelled so that
+/// the receiving thread stops after the first failure of another thread.
+pub struct CancellableReceiver<C, E> {
+    channel: C,
+    cancellation: Arc<Cancellation<E>>,
+    thread_id: usize,
+}
+
+impl<C, E> CancellableReceiver<C, E> {
+    pub fn new(channel: C, cancellation: Arc<Cancellation<E>>, thread_id: usize) -> Self {
+        Self {
+            channel,
+            cancellation,
+            thread_id,
+        }
+    }
+
+    pub fn recv<T>(&self) -> Result<T, RecvError>
+    where
+        C: ChannelReceiver<T>,
+    {
+        let result = if self.cancellation.is_cancelled() {
+            Err(RecvError)
+        } else {
+            self.channel.recv()
+        };
+        if result.is_err() {
+            self.cancellation.pipe_failed(self.thread_id);
+        }
+        result
+    }
+}
+
+impl<T, C: ChannelReceiver<T>, E> ChannelReceiver<T> for CancellableReceiver<C, E> {
+    fn recv(&self) -> Result<T, RecvError> {
+        CancellableReceiver::recv(self)
+    }
+}
+
+impl<R, C: ChannelReceiver<Option<R>>, E> RecordReceiver<R> for CancellableReceiver<C, E> {
+    fn recv(&self) -> Result<Option<R>, RecvError> {
+        CancellableReceiver::recv(self)
+    }
+}
+
+/// The sending half of a pipe, which reports being closed once the chain is cancelled so that
+/// the sending thread stops, even if it never receives anything, e.g. a source.
+pub struct CancellableSender<C, E> {
+    channel: C,
+    cancellation: Arc<Cancellation<E>>,
+    thread_id: usize,
+}
+
+impl<C, E> CancellableSender<C, E> {
+    pub fn new(channel: C, cancellation: Arc<Cancellation<E>>, thread_id: usize) -> Self {
+        Self {
+            channel,
+            cancellation,
+            thread_id,
+        }
+    }
+
+    pub fn send<T>(&self, value: T) -> Result<(), SendError<T>>
+    where
+        C: ChannelSender<T>,
+    {
+        let result = if self.cancellation.is_cancelled() {
+            Err(SendError(value))
+        } else {
+            self.channel.send(value)
+        };
+        if result.is_err() {
+            self.cancellation.pipe_failed(self.thread_id);
+        }
+        result
+    }
+}
+
+impl<T, C: ChannelSender<T>, E> ChannelSender<T> for CancellableSender<C, E> {
+    fn send(&self, value: T) -> Result<(), SendError<T>> {
+        CancellableSender::send(self, value)
+    }
+}
+
+/// The message of a panic payload, as passed to `panic!`.
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Box<dyn Any>".to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CancellableReceiver, CancellableSender, Cancellation, Interrupted};
+    use std::sync::{
+        mpsc::{sync_channel, RecvError},
+        Arc, Condvar, Mutex,
+    };
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Error {
+        Custom(&'static str),
+        Panicked(String),
+        Pipe,
+        Interrupted,
+    }
+
+    impl From<Interrupted> for Error {
+        fn from(_: Interrupted) -> Self {
+            Error::Interrupted
+        }
+    }
+
+    #[test]
+    fn should_succeed_without_failure() {
+        let cancellation = Cancellation::<Error>::new();
+        cancellation.run(0, "ok", || Ok(()));
+        assert!(!cancellation.is_cancelled());
+        assert_eq!(cancellation.result(Error::Panicked), Ok(()));
+    }
+
+    #[test]
+    fn should_prefer_originating_error_to_pipe_error() {
+        let cancellation = Cancellation::<Error>::new();
+        cancellation.pipe_failed(0);
+        cancellation.run(0, "reader", || Err(Error::Pipe));
+        assert!(cancellation.is_cancelled());
+        cancellation.run(1, "writer", || Err(Error::Custom("disk full")));
+        cancellation.run(2, "other", || Err(Error::Custom("later")));
+        let failure = cancellation.take_failure().unwrap();
+        assert_eq!(failure.thread_id, 1);
+        assert_eq!(failure.name, "writer");
+        assert_eq!(
+            failure.into_error(Error::Panicked),
+            Error::Custom("disk full")
+        );
+    }
+
+    #[test]
+    fn should_record_panic_and_interrupt() {
+        let cancellation = Cancellation::<Error>::new();
+        let interrupt = Arc::new((Mutex::new(false), Condvar::new()));
+        cancellation.add_interrupt(interrupt.clone());
+        cancellation.run(3, "panicking", || panic!("boom {}", 42));
+        assert!(*interrupt.0.lock().unwrap());
+        let failure = cancellation.take_failure().unwrap();
+        assert_eq!(failure.name, "panicking");
+        assert_matches!(failure.cause, super::ThreadFailureCause::Panic(message) if message == "boom 42");
+    }
+
+    #[test]
+    fn should_return_panic_as_error() {
+        let cancellation = Cancellation::<Error>::new();
+        cancellation.run(3, "panicking", || panic!("boom {}", 42));
+        assert_eq!(
+            cancellation.result(Error::Panicked),
+            Err(Error::Panicked(
+                "Thread 3 (panicking) panicked: boom 42".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn should_report_interruption_over_pipe_errors() {
+        let cancellation = Cancellation::<Error>::new();
+        let interrupt = Arc::new((Mutex::new(false), Condvar::new()));
+        cancellation.add_interrupt(interrupt.clone());
+        cancellation.interrupt();
+        assert!(cancellation.is_cancelled());
+        assert!(*interrupt.0.lock().unwrap());
+        cancellation.pipe_failed(0);
+        cancellation.run(0, "reader", || Err(Error::Pipe));
+        assert_eq!(
+            cancellation.result_or_interrupted(Error::Panicked),
+            Err(Error::Interrupted)
+        );
+
+        let cancellation = Cancellation::<Error>::new();
+        cancellation.interrupt();
+        cancellation.run(1, "writer", || Err(Error::Custom("disk full")));
+        assert_eq!(
+            cancellation.result_or_interrupted(Error::Panicked),
+            Err(Error::Custom("disk full"))
+        );
+
+        let cancellation = Cancellation::<Error>::new();
+        cancellation.run(2, "ok", || Ok(()));
+        assert_eq!(cancellation.result_or_interrupted(Error::Panicked), Ok(()));
+    }
+
+    #[test]
+    fn should_close_pipes_on_cancellation() {
+        let cancellation = Arc::new(Cancellation::<Error>::new());
+        let (tx, rx) = sync_channel(4);
+        let tx = CancellableSender::new(tx, cancellation.clone(), 0);
+        let rx = CancellableReceiver::new(rx, cancellation.clone(), 1);
+        tx.send(Some(1)).unwrap();
+        tx.send(Some(2)).unwrap();
+        assert_eq!(rx.recv(), Ok(Some(1)));
+
+        cancellation.run(2, "writer", || Err(Error::Custom("disk full")));
+        assert_eq!(rx.recv::<Option<i32>>(), Err(RecvError));
+        cancellation.run(1, "reader", || Err(Error::Pipe));
+        // The pipe still has room, but the chain is cancelled
+        assert!(tx.send(Some(3)).is_err());
+        cancellation.run(0, "producer", || Err(Error::Pipe));
+
+        let failure = cancellation.take_failure().unwrap();
+        assert_eq!(failure.name, "writer");
+    }
+}
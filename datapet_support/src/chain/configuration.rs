@@ -1,14 +1,182 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt::Display, path::PathBuf, str::FromStr, sync::Arc};
+
+use super::metrics::ChainMetrics;
+use crate::data::buffer::BufferCompression;
+
+#[derive(Error, PartialEq, Eq, Debug)]
+pub enum ConfigError {
+    #[error("Missing configuration value {name}")]
+    Missing { name: String },
+    #[error("Invalid configuration value {name}={value:?}: {msg}")]
+    Invalid {
+        name: String,
+        value: String,
+        msg: String,
+    },
+    #[error("Unexpected argument {0:?}")]
+    UnexpectedArgument(String),
+}
 
 pub struct ChainConfiguration {
     pub variables: BTreeMap<String, String>,
+    /// Overrides the capacity of all the channels between threads.
+    pub channel_capacity: Option<usize>,
+    /// Overrides the capacity of the channels by the name of their source, e.g.
+    /// `dtpt_main::read`. Takes precedence over `channel_capacity`.
+    pub channel_capacities: BTreeMap<String, usize>,
+    /// Overrides the number of worker threads of all the parallel filters.
+    pub worker_count: Option<usize>,
+    /// Overrides the number of worker threads by filter name, e.g. `dtpt_main::stem`. Takes
+    /// precedence over `worker_count`.
+    pub worker_counts: BTreeMap<String, usize>,
+    /// Overrides the memory budget in bytes of all the sorts.
+    pub sort_memory_budget: Option<usize>,
+    /// Overrides the memory budget in bytes of the sorts by filter name. Takes precedence over
+    /// `sort_memory_budget`.
+    pub sort_memory_budgets: BTreeMap<String, usize>,
+    /// The directory of the files spilled to disk, the system temporary directory if not set.
+    pub spill_dir: Option<PathBuf>,
+    /// Overrides the compression of the records spilled by all the sorts and accumulations,
+    /// including the ones inserted in auto-order mode.
+    pub spill_compression: Option<BufferCompression>,
+    /// Overrides the compression of the spilled records by filter name. Takes precedence over
+    /// `spill_compression`.
+    pub spill_compressions: BTreeMap<String, BufferCompression>,
+    /// Overrides the path of the files read or written by filter name, e.g. `dtpt_main::write`.
+    pub paths: BTreeMap<String, PathBuf>,
+    /// The metrics of the streams, recorded if the chain was generated with stream metrics. Keep
+    /// a clone to read them during or after the run.
+    pub metrics: Arc<ChainMetrics>,
 }
 
 impl ChainConfiguration {
     pub fn new() -> Self {
         Self {
             variables: BTreeMap::new(),
+            channel_capacity: None,
+            channel_capacities: BTreeMap::new(),
+            worker_count: None,
+            worker_counts: BTreeMap::new(),
+            sort_memory_budget: None,
+            sort_memory_budgets: BTreeMap::new(),
+            spill_dir: None,
+            spill_compression: None,
+            spill_compressions: BTreeMap::new(),
+            paths: BTreeMap::new(),
+            metrics: Arc::default(),
+        }
+    }
+
+    /// Reads the variables declared by a chain from command line arguments of the form
+    /// `--name=value` or `--name value`, dashes in names standing for underscores.
+    pub fn from_args<I>(args: I, names: &[&str]) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut configuration = Self::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.strip_prefix("--") {
+                Some(name_value) => match name_value.split_once('=') {
+                    Some((name, value)) => (name.replace('-', "_"), Some(value.to_owned())),
+                    None => (name_value.replace('-', "_"), None),
+                },
+                None => return Err(ConfigError::UnexpectedArgument(arg)),
+            };
+            if !names.contains(&name.as_str()) {
+                return Err(ConfigError::UnexpectedArgument(arg));
+            }
+            let value = match value {
+                Some(value) => value,
+                None => args
+                    .next()
+                    .ok_or_else(|| ConfigError::Missing { name: name.clone() })?,
+            };
+            configuration.variables.insert(name, value);
+        }
+        Ok(configuration)
+    }
+
+    /// Reads the variables declared by a chain from the environment, the variable `name` being
+    /// read from `{prefix}{NAME}`.
+    pub fn from_env(prefix: &str, names: &[&str]) -> Self {
+        let mut configuration = Self::new();
+        for name in names {
+            if let Ok(value) = std::env::var(format!("{}{}", prefix, name.to_uppercase())) {
+                configuration.variables.insert((*name).to_owned(), value);
+            }
         }
+        configuration
+    }
+
+    /// Parses the variable `name`, `default` being the value declared by the chain if any.
+    pub fn parse_variable<T>(&self, name: &str, default: Option<&str>) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self
+            .variables
+            .get(name)
+            .map(String::as_str)
+            .or(default)
+            .ok_or_else(|| ConfigError::Missing {
+                name: name.to_owned(),
+            })?;
+        value.parse().map_err(|err: T::Err| ConfigError::Invalid {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            msg: err.to_string(),
+        })
+    }
+
+    /// The capacity of the channel carrying the records of `source`, `default` being the one
+    /// chosen when the chain was generated.
+    pub fn channel_capacity(&self, source: &str, default: usize) -> usize {
+        self.channel_capacities
+            .get(source)
+            .copied()
+            .or(self.channel_capacity)
+            .unwrap_or(default)
+    }
+
+    /// The number of worker threads of the parallel filter `name`, `default` being the one
+    /// chosen when the chain was generated.
+    pub fn worker_count(&self, name: &str, default: usize) -> usize {
+        self.worker_counts
+            .get(name)
+            .copied()
+            .or(self.worker_count)
+            .unwrap_or(default)
+    }
+
+    /// The memory budget of the sort filter `name`, `default` being the one chosen when the chain
+    /// was generated.
+    pub fn sort_memory_budget(&self, name: &str, default: Option<usize>) -> Option<usize> {
+        self.sort_memory_budgets
+            .get(name)
+            .copied()
+            .or(self.sort_memory_budget)
+            .or(default)
+    }
+
+    /// The compression of the records spilled by the filter `name`, `default` being the one
+    /// chosen when the chain was generated.
+    pub fn spill_compression(&self, name: &str, default: BufferCompression) -> BufferCompression {
+        self.spill_compressions
+            .get(name)
+            .copied()
+            .or(self.spill_compression)
+            .unwrap_or(default)
+    }
+
+    /// The path of the file read or written by the filter `name`, `default` being the one chosen
+    /// when the chain was generated.
+    pub fn path(&self, name: &str, default: &str) -> PathBuf {
+        self.paths
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from(default))
     }
 }
 
@@ -17,3 +185,101 @@ impl Default for ChainConfiguration {
         Self::new()
     }
 }
+
+#[test]
+fn should_override_channel_capacity() {
+    let mut configuration = ChainConfiguration::new();
+    assert_eq!(configuration.channel_capacity("main::read", 42), 42);
+    configuration.channel_capacity = Some(8);
+    assert_eq!(configuration.channel_capacity("main::read", 42), 8);
+    configuration
+        .channel_capacities
+        .insert("main::read".to_owned(), 1);
+    assert_eq!(configuration.channel_capacity("main::read", 42), 1);
+    assert_eq!(configuration.channel_capacity("main::sort", 42), 8);
+}
+
+#[test]
+fn should_override_spill_compression() {
+    let mut configuration = ChainConfiguration::new();
+    assert_eq!(
+        configuration.spill_compression("main::sort", BufferCompression::Lz4),
+        BufferCompression::Lz4
+    );
+    configuration.spill_compression = Some(BufferCompression::Zstd);
+    configuration
+        .spill_compressions
+        .insert("main::group_auto_sort".to_owned(), BufferCompression::None);
+    assert_eq!(
+        configuration.spill_compression("main::sort", BufferCompression::Lz4),
+        BufferCompression::Zstd
+    );
+    assert_eq!(
+        configuration.spill_compression("main::group_auto_sort", BufferCompression::Lz4),
+        BufferCompression::None
+    );
+}
+
+#[test]
+fn should_override_path() {
+    let mut configuration = ChainConfiguration::new();
+    assert_eq!(
+        configuration.path("main::write", ":memory:"),
+        PathBuf::from(":memory:")
+    );
+    configuration
+        .paths
+        .insert("main::write".to_owned(), PathBuf::from("/tmp/out.db"));
+    assert_eq!(
+        configuration.path("main::write", ":memory:"),
+        PathBuf::from("/tmp/out.db")
+    );
+    assert_eq!(
+        configuration.path("main::read", ":memory:"),
+        PathBuf::from(":memory:")
+    );
+}
+
+#[test]
+fn should_parse_variables_from_args() {
+    let args = ["--count=3", "--spill-dir", "/tmp"].map(ToString::to_string);
+    let configuration = ChainConfiguration::from_args(args, &["count", "spill_dir"]).unwrap();
+    assert_eq!(configuration.parse_variable::<usize>("count", None), Ok(3));
+    assert_eq!(
+        configuration.parse_variable::<String>("spill_dir", None),
+        Ok("/tmp".to_owned())
+    );
+    assert_eq!(
+        configuration.parse_variable::<u8>("other", Some("7")),
+        Ok(7)
+    );
+
+    assert_eq!(
+        ChainConfiguration::from_args(["--unknown=1".to_owned()], &["count"]).err(),
+        Some(ConfigError::UnexpectedArgument("--unknown=1".to_owned()))
+    );
+    assert_eq!(
+        ChainConfiguration::from_args(["--count".to_owned()], &["count"]).err(),
+        Some(ConfigError::Missing {
+            name: "count".to_owned()
+        })
+    );
+}
+
+#[test]
+fn should_report_invalid_variables() {
+    let mut configuration = ChainConfiguration::new();
+    assert_eq!(
+        configuration.parse_variable::<usize>("count", None),
+        Err(ConfigError::Missing {
+            name: "count".to_owned()
+        })
+    );
+    configuration
+        .variables
+        .insert("count".to_owned(), "many".to_owned());
+    assert_matches!(
+        configuration.parse_variable::<usize>("count", Some("1")),
+        Err(ConfigError::Invalid { name, value, .. }) if name == "count" && value == "many"
+    );
+}
@@ -0,0 +1,103 @@
+use fallible_iterator::FallibleIterator;
+
+use super::cancellation::ThreadFailure;
+
+/// Chain error types which can carry the context of the filter which failed.
+pub trait ErrorContext: Sized {
+    /// Wraps the error with the name of the filter, the id of its thread and the index of the
+    /// record being processed, if known.
+    ///
+    /// An error which already has a context keeps it, the innermost filter being the one which
+    /// failed.
+    fn with_context(
+        self,
+        name: &'static str,
+        thread_id: usize,
+        record_index: Option<usize>,
+    ) -> Self;
+
+    /// Converts the failure of a thread, possibly a panic, into the error returned by the chain.
+    fn thread_failed(failure: ThreadFailure<Self>) -> Self;
+}
+
+/// Counts the records of a filter output and adds context to its errors.
+pub struct ContextIterator<I> {
+    input: I,
+    name: &'static str,
+    thread_id: usize,
+    record_index: usize,
+}
+
+impl<I> ContextIterator<I> {
+    pub fn new(input: I, name: &'static str, thread_id: usize) -> Self {
+        Self {
+            input,
+            name,
+            thread_id,
+            record_index: 0,
+        }
+    }
+}
+
+impl<I> FallibleIterator for ContextIterator<I>
+where
+    I: FallibleIterator,
+    I::Error: ErrorContext,
+{
+    type Item = I::Item;
+    type Error = I::Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        match self.input.next() {
+            Ok(record) => {
+                if record.is_some() {
+                    self.record_index += 1;
+                }
+                Ok(record)
+            }
+            Err(err) => Err(err.with_context(self.name, self.thread_id, Some(self.record_index))),
+        }
+    }
+}
+
+#[test]
+fn should_add_record_index_to_error() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Error {
+        Custom(&'static str),
+        Context(&'static str, usize, Option<usize>, Box<Error>),
+    }
+
+    impl ErrorContext for Error {
+        fn with_context(
+            self,
+            name: &'static str,
+            thread_id: usize,
+            record_index: Option<usize>,
+        ) -> Self {
+            match self {
+                Error::Context(..) => self,
+                _ => Error::Context(name, thread_id, record_index, Box::new(self)),
+            }
+        }
+
+        fn thread_failed(failure: ThreadFailure<Self>) -> Self {
+            failure.into_error(|_| Error::Custom("panicked"))
+        }
+    }
+
+    let input = fallible_iterator::convert(vec![Ok(1), Ok(2), Err(Error::Custom("bad record"))]);
+    let inner = ContextIterator::new(input, "main::parse", 1);
+    let mut outer = ContextIterator::new(inner, "main::sort", 1);
+    assert_eq!(outer.next(), Ok(Some(1)));
+    assert_eq!(outer.next(), Ok(Some(2)));
+    assert_eq!(
+        outer.next(),
+        Err(Error::Context(
+            "main::parse",
+            1,
+            Some(2),
+            Box::new(Error::Custom("bad record"))
+        ))
+    );
+}
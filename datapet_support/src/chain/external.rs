@@ -0,0 +1,58 @@
+use std::{sync::mpsc::Receiver, thread::JoinHandle};
+
+use fallible_iterator::FallibleIterator;
+
+use crate::iterator::sync::mpsc::Receive;
+
+/// The records fed to an external input of a chain.
+pub type ExternalInput<R, E> = Box<dyn FallibleIterator<Item = R, Error = E> + Send>;
+
+/// The records of an external output of a chain.
+pub type ExternalOutput<R, E, RX = Receiver<Option<R>>> = Receive<R, E, RX>;
+
+/// A chain started with external inputs and outputs.
+pub struct ChainRun<E> {
+    state: ChainRunState<E>,
+}
+
+enum ChainRunState<E> {
+    Running(JoinHandle<Result<(), E>>),
+    Done(Result<(), E>),
+}
+
+impl<E: Send + 'static> ChainRun<E> {
+    /// Runs the chain in the background.
+    pub fn spawn<F>(run: F) -> Self
+    where
+        F: FnOnce() -> Result<(), E> + Send + 'static,
+    {
+        Self {
+            state: ChainRunState::Running(std::thread::spawn(run)),
+        }
+    }
+
+    /// Wraps the result of a chain which already ran.
+    pub fn done(result: Result<(), E>) -> Self {
+        Self {
+            state: ChainRunState::Done(result),
+        }
+    }
+
+    /// Waits for the end of the chain and returns its result.
+    ///
+    /// The external outputs must be consumed or dropped beforehand, otherwise the chain may wait
+    /// forever for room in their channels. Dropping an external output discards its records.
+    pub fn wait(self) -> Result<(), E> {
+        match self.state {
+            ChainRunState::Running(handle) => handle.join().expect("chain"),
+            ChainRunState::Done(result) => result,
+        }
+    }
+}
+
+#[test]
+fn should_wait_for_chain_result() {
+    let run = ChainRun::spawn(|| Err::<(), _>("failure"));
+    assert_eq!(run.wait(), Err("failure"));
+    assert_eq!(ChainRun::<&str>::done(Ok(())).wait(), Ok(()));
+}
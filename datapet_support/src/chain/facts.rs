@@ -0,0 +1,282 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fmt::Debug,
+    sync::{mpsc::RecvError, Arc},
+};
+
+use fallible_iterator::FallibleIterator;
+
+use crate::{
+    chain::cancellation::{Cancellation, ThreadFailure, ThreadFailureCause},
+    iterator::sync::mpsc::RecordReceiver,
+};
+
+/// A stream whose records do not respect its declared order or distinct fact.
+#[derive(Error, Clone, PartialEq, Eq, Debug)]
+#[error(
+    r#"Stream "{stream}" breaks its {fact} fact at record {record_index}: {previous} then {current}"#
+)]
+pub struct FactViolation {
+    pub stream: &'static str,
+    /// Either `order` or `distinct`.
+    pub fact: &'static str,
+    pub record_index: usize,
+    /// The key of the fact in the previous (or a previous) record.
+    pub previous: String,
+    /// The key of the fact in the offending record.
+    pub current: String,
+}
+
+/// The way the distinct fact of a stream is checked.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DistinctCheck {
+    /// The stream has no distinct fact.
+    None,
+    /// The stream is ordered by its distinct fields first, so that duplicates are adjacent.
+    Adjacent,
+    /// Every distinct key of the stream is kept in memory.
+    Set,
+}
+
+/// Checks the facts of a stream from the keys of its records.
+///
+/// The order key is a tuple of the order fields, descending ones being wrapped in
+/// [`std::cmp::Reverse`], so that the records are ordered if their keys are.
+pub struct FactsChecker<O, D> {
+    stream: &'static str,
+    distinct: DistinctCheck,
+    record_index: usize,
+    previous: Option<(O, D)>,
+    seen: BTreeSet<D>,
+}
+
+impl<O, D> FactsChecker<O, D>
+where
+    O: Ord + Debug,
+    D: Ord + Clone + Debug,
+{
+    pub fn new(stream: &'static str, distinct: DistinctCheck) -> Self {
+        Self {
+            stream,
+            distinct,
+            record_index: 0,
+            previous: None,
+            seen: BTreeSet::new(),
+        }
+    }
+
+    pub fn check(&mut self, order_key: O, distinct_key: D) -> Result<(), FactViolation> {
+        let record_index = self.record_index;
+        self.record_index += 1;
+        if let Some((previous_order_key, previous_distinct_key)) = &self.previous {
+            if *previous_order_key > order_key {
+                return Err(self.violation("order", record_index, previous_order_key, &order_key));
+            }
+            if self.distinct == DistinctCheck::Adjacent && *previous_distinct_key == distinct_key {
+                return Err(self.violation(
+                    "distinct",
+                    record_index,
+                    previous_distinct_key,
+                    &distinct_key,
+                ));
+            }
+        }
+        if self.distinct == DistinctCheck::Set {
+            if let Some(previous_distinct_key) = self.seen.get(&distinct_key) {
+                return Err(self.violation(
+                    "distinct",
+                    record_index,
+                    previous_distinct_key,
+                    &distinct_key,
+                ));
+            }
+            self.seen.insert(distinct_key.clone());
+        }
+        self.previous = Some((order_key, distinct_key));
+        Ok(())
+    }
+
+    fn violation<K: Debug>(
+        &self,
+        fact: &'static str,
+        record_index: usize,
+        previous: &K,
+        current: &K,
+    ) -> FactViolation {
+        FactViolation {
+            stream: self.stream,
+            fact,
+            record_index,
+            previous: format!("{:?}", previous),
+            current: format!("{:?}", current),
+        }
+    }
+}
+
+/// Checks the facts of a stream, record by record.
+pub type RecordCheck<R> = Box<dyn FnMut(&R) -> Result<(), FactViolation> + Send>;
+
+/// Builds the check of the records of a stream from the function extracting their keys.
+pub fn record_check<R, O, D, K>(mut checker: FactsChecker<O, D>, keys: K) -> RecordCheck<R>
+where
+    O: Ord + Debug + Send + 'static,
+    D: Ord + Clone + Debug + Send + 'static,
+    K: Fn(&R) -> (O, D) + Send + 'static,
+{
+    Box::new(move |record| {
+        let (order_key, distinct_key) = keys(record);
+        checker.check(order_key, distinct_key)
+    })
+}
+
+/// Checks the facts of the stream produced by an inline node, if a check is given.
+pub struct FactsCheckIterator<I: FallibleIterator> {
+    iter: I,
+    check: Option<RecordCheck<I::Item>>,
+}
+
+impl<I: FallibleIterator> FactsCheckIterator<I> {
+    pub fn new(iter: I, check: Option<RecordCheck<I::Item>>) -> Self {
+        Self { iter, check }
+    }
+}
+
+impl<I> FallibleIterator for FactsCheckIterator<I>
+where
+    I: FallibleIterator,
+    I::Error: From<FactViolation>,
+{
+    type Item = I::Item;
+    type Error = I::Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        let next = self.iter.next()?;
+        if let (Some(record), Some(check)) = (next.as_ref(), self.check.as_mut()) {
+            check(record)?;
+        }
+        Ok(next)
+    }
+}
+
+/// Checks the facts of a stream received from another thread, if a check is given.
+///
+/// A violation fails the receiving thread through the cancellation of the chain, then the
+/// receiver reports a closed pipe so that the thread stops.
+pub struct FactsCheckReceiver<C, R, E> {
+    channel: C,
+    check: Option<RefCell<RecordCheck<R>>>,
+    cancellation: Arc<Cancellation<E>>,
+    thread_id: usize,
+    name: &'static str,
+}
+
+impl<C, R, E> FactsCheckReceiver<C, R, E>
+where
+    C: RecordReceiver<R>,
+    E: From<FactViolation>,
+{
+    pub fn new(
+        channel: C,
+        check: Option<RecordCheck<R>>,
+        cancellation: Arc<Cancellation<E>>,
+        thread_id: usize,
+        name: &'static str,
+    ) -> Self {
+        Self {
+            channel,
+            check: check.map(RefCell::new),
+            cancellation,
+            thread_id,
+            name,
+        }
+    }
+
+    pub fn recv(&self) -> Result<Option<R>, RecvError> {
+        let record = self.channel.recv()?;
+        if let (Some(record), Some(check)) = (record.as_ref(), self.check.as_ref()) {
+            if let Err(violation) = (check.borrow_mut())(record) {
+                self.cancellation.fail(ThreadFailure {
+                    thread_id: self.thread_id,
+                    name: self.name,
+                    cause: ThreadFailureCause::Error(violation.into()),
+                });
+                return Err(RecvError);
+            }
+        }
+        Ok(record)
+    }
+}
+
+impl<C, R, E> RecordReceiver<R> for FactsCheckReceiver<C, R, E>
+where
+    C: RecordReceiver<R>,
+    E: From<FactViolation>,
+{
+    fn recv(&self) -> Result<Option<R>, RecvError> {
+        FactsCheckReceiver::recv(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Reverse;
+
+    use fallible_iterator::FallibleIterator;
+
+    use super::{record_check, DistinctCheck, FactViolation, FactsCheckIterator, FactsChecker};
+
+    #[test]
+    fn should_report_order_violation() {
+        let mut checker = FactsChecker::new("test::order", DistinctCheck::None);
+        assert_eq!(checker.check((1, Reverse(3)), ()), Ok(()));
+        assert_eq!(checker.check((1, Reverse(2)), ()), Ok(()));
+        assert_eq!(checker.check((2, Reverse(5)), ()), Ok(()));
+        assert_eq!(
+            checker.check((2, Reverse(6)), ()),
+            Err(FactViolation {
+                stream: "test::order",
+                fact: "order",
+                record_index: 3,
+                previous: "(2, Reverse(5))".to_string(),
+                current: "(2, Reverse(6))".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_adjacent_duplicate() {
+        let mut checker = FactsChecker::new("test::adjacent", DistinctCheck::Adjacent);
+        assert_eq!(checker.check((1,), (1,)), Ok(()));
+        assert_eq!(checker.check((2,), (2,)), Ok(()));
+        assert_matches!(
+            checker.check((2,), (2,)),
+            Err(FactViolation {
+                fact: "distinct",
+                record_index: 2,
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn should_report_any_duplicate() {
+        let mut iter = FactsCheckIterator::new(
+            fallible_iterator::convert(["a", "b", "a"].into_iter().map(Ok::<_, FactViolation>)),
+            Some(record_check(
+                FactsChecker::new("test::set", DistinctCheck::Set),
+                |record: &&str| ((), (record.to_string(),)),
+            )),
+        );
+        assert_eq!(iter.next(), Ok(Some("a")));
+        assert_eq!(iter.next(), Ok(Some("b")));
+        assert_matches!(
+            iter.next(),
+            Err(FactViolation {
+                fact: "distinct",
+                record_index: 2,
+                ..
+            })
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{RecvError, SendError},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use fallible_iterator::FallibleIterator;
+
+use crate::iterator::sync::mpsc::{ChannelReceiver, ChannelSender, RecordReceiver};
+
+/// The metrics of the streams of a chain run, by node name.
+///
+/// They are held by the configuration of the chain, so that each run records its own metrics,
+/// which can be read during or after the run through a clone of
+/// [`ChainConfiguration::metrics`](super::configuration::ChainConfiguration::metrics).
+#[derive(Default, Debug)]
+pub struct ChainMetrics {
+    streams: Mutex<BTreeMap<String, Arc<StreamMetrics>>>,
+}
+
+impl ChainMetrics {
+    /// The metrics of the stream of the node `name`, registered on first use.
+    pub fn stream_metrics(&self, name: &str) -> Arc<StreamMetrics> {
+        self.streams
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entry(name.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    /// The metrics of the channel carrying the stream of the node `name`, registered on first
+    /// use.
+    pub fn channel_metrics(&self, name: &str, capacity: usize) -> Arc<StreamMetrics> {
+        let metrics = self.stream_metrics(name);
+        metrics.capacity.store(capacity, Ordering::Relaxed);
+        metrics
+    }
+
+    /// The current metrics of all the streams, by node name.
+    pub fn snapshot(&self) -> BTreeMap<String, StreamMetricsSnapshot> {
+        self.streams
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .map(|(name, metrics)| (name.clone(), metrics.snapshot()))
+            .collect()
+    }
+}
+
+/// The metrics of the stream produced by a node.
+#[derive(Default, Debug)]
+pub struct StreamMetrics {
+    capacity: AtomicUsize,
+    records_produced: AtomicU64,
+    records_sent: AtomicU64,
+    records_received: AtomicU64,
+    send_nanos: AtomicU64,
+    recv_nanos: AtomicU64,
+    queued: AtomicI64,
+}
+
+/// The state of the metrics of a stream at some point in time.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StreamMetricsSnapshot {
+    /// The capacity of the channel carrying the stream to another thread, 0 if it does not leave
+    /// the thread of the node.
+    pub capacity: usize,
+    /// The records produced by an inline node, i.e. one which runs in the thread of its input, or
+    /// mapped by the workers of a parallel node.
+    pub records_produced: u64,
+    /// The records sent through the channel.
+    pub records_sent: u64,
+    /// The records received from the channel.
+    pub records_received: u64,
+    /// The time spent sending to the channel, including waiting for it to have room.
+    pub send_time: Duration,
+    /// The time spent receiving from the channel, including waiting for records.
+    pub recv_time: Duration,
+    /// The number of messages in the channel, batches if records are batched.
+    pub queued: u64,
+}
+
+impl StreamMetrics {
+    /// Counts a record produced by the node.
+    pub fn record_produced(&self) {
+        self.records_produced.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StreamMetricsSnapshot {
+        StreamMetricsSnapshot {
+            capacity: self.capacity.load(Ordering::Relaxed),
+            records_produced: self.records_produced.load(Ordering::Relaxed),
+            records_sent: self.records_sent.load(Ordering::Relaxed),
+            records_received: self.records_received.load(Ordering::Relaxed),
+            send_time: Duration::from_nanos(self.send_nanos.load(Ordering::Relaxed)),
+            recv_time: Duration::from_nanos(self.recv_nanos.load(Ordering::Relaxed)),
+            // A message can be received before its sender accounts for it
+            queued: self.queued.load(Ordering::Relaxed).max(0) as u64,
+        }
+    }
+}
+
+/// A message sent through a channel between threads.
+pub trait ChannelMessage {
+    fn record_count(&self) -> usize;
+}
+
+impl<R> ChannelMessage for Option<R> {
+    fn record_count(&self) -> usize {
+        self.is_some().into()
+    }
+}
+
+impl<R> ChannelMessage for Vec<R> {
+    fn record_count(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Either half of a channel, recording the traffic into the metrics of its stream.
+pub struct Metered<C> {
+    channel: C,
+    metrics: Arc<StreamMetrics>,
+}
+
+impl<C> Metered<C> {
+    pub fn new(channel: C, metrics: Arc<StreamMetrics>) -> Self {
+        Self { channel, metrics }
+    }
+
+    pub fn send<T>(&self, value: T) -> Result<(), SendError<T>>
+    where
+        C: ChannelSender<T>,
+        T: ChannelMessage,
+    {
+        let records = value.record_count() as u64;
+        let start = Instant::now();
+        let result = self.channel.send(value);
+        add_elapsed(&self.metrics.send_nanos, start);
+        if result.is_ok() {
+            self.metrics
+                .records_sent
+                .fetch_add(records, Ordering::Relaxed);
+            self.metrics.queued.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn recv<T>(&self) -> Result<T, RecvError>
+    where
+        C: ChannelReceiver<T>,
+        T: ChannelMessage,
+    {
+        let start = Instant::now();
+        let result = self.channel.recv();
+        add_elapsed(&self.metrics.recv_nanos, start);
+        if let Ok(value) = &result {
+            self.metrics
+                .records_received
+                .fetch_add(value.record_count() as u64, Ordering::Relaxed);
+            self.metrics.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+fn add_elapsed(nanos: &AtomicU64, start: Instant) {
+    let elapsed = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+    nanos.fetch_add(elapsed, Ordering::Relaxed);
+}
+
+impl<T: ChannelMessage, C: ChannelSender<T>> ChannelSender<T> for Metered<C> {
+    fn send(&self, value: T) -> Result<(), SendError<T>> {
+        Metered::send(self, value)
+    }
+}
+
+impl<T: ChannelMessage, C: ChannelReceiver<T>> ChannelReceiver<T> for Metered<C> {
+    fn recv(&self) -> Result<T, RecvError> {
+        Metered::recv(self)
+    }
+}
+
+impl<R, C: ChannelReceiver<Option<R>>> RecordReceiver<R> for Metered<C> {
+    fn recv(&self) -> Result<Option<R>, RecvError> {
+        Metered::recv(self)
+    }
+}
+
+/// Counts the records produced by an inline node.
+pub struct MeteredIterator<I> {
+    iter: I,
+    metrics: Arc<StreamMetrics>,
+}
+
+impl<I> MeteredIterator<I> {
+    pub fn new(iter: I, metrics: Arc<StreamMetrics>) -> Self {
+        Self { iter, metrics }
+    }
+}
+
+impl<I: FallibleIterator> FallibleIterator for MeteredIterator<I> {
+    type Item = I::Item;
+    type Error = I::Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        let next = self.iter.next()?;
+        if next.is_some() {
+            self.metrics.record_produced();
+        }
+        Ok(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{mpsc::sync_channel, Arc};
+
+    use fallible_iterator::FallibleIterator;
+
+    use super::{ChainMetrics, Metered, MeteredIterator, StreamMetrics};
+    use crate::iterator::sync::mpsc::{BatchReceiver, BatchSender};
+
+    #[test]
+    fn should_meter_channel() {
+        let metrics = Arc::new(StreamMetrics::default());
+        let (tx, rx) = sync_channel(4);
+        let tx = Metered::new(tx, metrics.clone());
+        let rx = Metered::new(rx, metrics.clone());
+
+        tx.send(Some(1)).unwrap();
+        tx.send(Some(2)).unwrap();
+        tx.send(None).unwrap();
+        assert_eq!(metrics.snapshot().records_sent, 2);
+        assert_eq!(metrics.snapshot().queued, 3);
+
+        assert_eq!(rx.recv().unwrap(), Some(1));
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.records_received, 1);
+        assert_eq!(snapshot.queued, 2);
+    }
+
+    #[test]
+    fn should_count_batched_records() {
+        let metrics = Arc::new(StreamMetrics::default());
+        let (tx, rx) = sync_channel(4);
+        let tx = BatchSender::new(Metered::new(tx, metrics.clone()), 2);
+        let rx = BatchReceiver::new(Metered::new(rx, metrics.clone()));
+
+        for i in 0..3 {
+            tx.send(Some(i)).unwrap();
+        }
+        tx.send(None).unwrap();
+        while rx.recv().unwrap().is_some() {}
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.records_sent, 3);
+        assert_eq!(snapshot.records_received, 3);
+        assert_eq!(snapshot.queued, 0);
+    }
+
+    #[test]
+    fn should_register_inline_node_metrics() {
+        let chain_metrics = ChainMetrics::default();
+        let mut iter = MeteredIterator::new(
+            fallible_iterator::convert((0..5).map(Ok::<_, ()>)),
+            chain_metrics.stream_metrics("main::update"),
+        );
+        while iter.next().unwrap().is_some() {}
+        assert_eq!(chain_metrics.snapshot()["main::update"].records_produced, 5);
+
+        // The metrics of another run are recorded separately
+        assert!(ChainMetrics::default().snapshot().is_empty());
+    }
+}
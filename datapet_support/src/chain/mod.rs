@@ -1 +1,10 @@
+pub mod cancellation;
 pub mod configuration;
+pub mod context;
+pub mod external;
+pub mod facts;
+pub mod metrics;
+#[cfg(feature = "signal-hook")]
+pub mod signal;
+#[cfg(feature = "tracing")]
+pub mod tracing;
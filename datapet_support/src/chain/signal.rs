@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use super::cancellation::Cancellation;
+
+/// Interrupts a chain when the process receives SIGINT or SIGTERM, for as long as the guard is
+/// alive. A second signal terminates the process immediately.
+///
+/// Signals are only handled on Unix, the guard does nothing on other platforms.
+pub struct SignalGuard {
+    #[cfg(unix)]
+    handle: signal_hook::iterator::Handle,
+    #[cfg(unix)]
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl SignalGuard {
+    pub fn install<E>(cancellation: Arc<Cancellation<E>>) -> std::io::Result<Self>
+    where
+        E: Send + 'static,
+    {
+        #[cfg(unix)]
+        {
+            use signal_hook::{
+                consts::{SIGINT, SIGTERM},
+                iterator::Signals,
+            };
+
+            let mut signals = Signals::new([SIGINT, SIGTERM])?;
+            let handle = signals.handle();
+            let thread = std::thread::spawn(move || {
+                for signal in signals.forever() {
+                    if cancellation.is_interrupted() {
+                        std::process::exit(128 + signal);
+                    }
+                    cancellation.interrupt();
+                }
+            });
+            Ok(Self {
+                handle,
+                thread: Some(thread),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            drop(cancellation);
+            Ok(Self {})
+        }
+    }
+}
+
+impl Drop for SignalGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            self.handle.close();
+            if let Some(thread) = self.thread.take() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use super::SignalGuard;
+    use crate::{chain::cancellation::Cancellation, DatapetError};
+
+    #[test]
+    fn should_interrupt_chain_on_signal() {
+        let cancellation = Arc::new(Cancellation::<DatapetError>::new());
+        let guard = SignalGuard::install(cancellation.clone()).unwrap();
+        signal_hook::low_level::raise(signal_hook::consts::SIGTERM).unwrap();
+        for _ in 0..100 {
+            if cancellation.is_interrupted() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(guard);
+        assert!(cancellation.is_cancelled());
+        assert_matches!(
+            cancellation.result_or_interrupted(DatapetError::custom),
+            Err(DatapetError::Interrupted(_))
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt::Display;
+
+use ::tracing::{debug, error, Span};
+use fallible_iterator::FallibleIterator;
+
+/// Runs the main of a thread in its span, with events for its start, its end and its error.
+pub fn run_traced<E, F>(span: Span, thread_main: F) -> Result<(), E>
+where
+    E: Display,
+    F: FnOnce() -> Result<(), E>,
+{
+    let _entered = span.enter();
+    debug!("thread start");
+    let result = thread_main();
+    match &result {
+        Ok(()) => debug!("thread end"),
+        Err(err) => error!(error = %err, "thread failed"),
+    }
+    result
+}
+
+/// Emits the events of the stream produced by an inline node in its span: start, end of stream
+/// with the record count, and errors.
+///
+/// Errors are reported at the debug level because they are also reported by the thread main.
+pub struct TracedIterator<I> {
+    iter: I,
+    span: Span,
+    records: usize,
+    started: bool,
+}
+
+impl<I> TracedIterator<I> {
+    pub fn new(iter: I, span: Span) -> Self {
+        Self {
+            iter,
+            span,
+            records: 0,
+            started: false,
+        }
+    }
+}
+
+impl<I> FallibleIterator for TracedIterator<I>
+where
+    I: FallibleIterator,
+    I::Error: Display,
+{
+    type Item = I::Item;
+    type Error = I::Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        let _entered = self.span.enter();
+        if !self.started {
+            self.started = true;
+            debug!("stream start");
+        }
+        match self.iter.next() {
+            Ok(Some(record)) => {
+                self.records += 1;
+                Ok(Some(record))
+            }
+            Ok(None) => {
+                debug!(records = self.records, "end of stream");
+                Ok(None)
+            }
+            Err(err) => {
+                debug!(error = %err, records = self.records, "stream failed");
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt::{Debug, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use ::tracing::{
+        field::{Field, Visit},
+        info_span,
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+    use fallible_iterator::FallibleIterator;
+
+    use super::{run_traced, TracedIterator};
+
+    /// The events recorded by a [`Recorder`], prefixed with the name of their span, e.g.
+    /// `test::thread: thread start`.
+    #[derive(Default)]
+    struct Recording {
+        spans: Mutex<Vec<&'static str>>,
+        entered: Mutex<Vec<u64>>,
+        events: Mutex<Vec<String>>,
+    }
+
+    struct Recorder(Arc<Recording>);
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.0.spans.lock().unwrap();
+            spans.push(span.metadata().name());
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let span = match self.0.entered.lock().unwrap().last() {
+                Some(id) => self.0.spans.lock().unwrap()[*id as usize - 1],
+                None => "none",
+            };
+            let mut fields = EventFields::default();
+            event.record(&mut fields);
+            self.0
+                .events
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}{}", span, fields.message, fields.others));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.0.entered.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.0.entered.lock().unwrap().pop();
+        }
+    }
+
+    #[derive(Default)]
+    struct EventFields {
+        message: String,
+        others: String,
+    }
+
+    impl Visit for EventFields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == "message" {
+                write!(self.message, "{:?}", value).unwrap();
+            } else {
+                write!(self.others, " {}={:?}", field.name(), value).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn should_pass_records_and_errors_through() {
+        let mut iter = TracedIterator::new(
+            fallible_iterator::convert(vec![Ok(1), Ok(2), Err("boom")].into_iter()),
+            info_span!("test::traced", thread_id = 0),
+        );
+        assert_eq!(iter.next(), Ok(Some(1)));
+        assert_eq!(iter.next(), Ok(Some(2)));
+        assert_eq!(iter.next(), Err("boom"));
+
+        assert_eq!(
+            run_traced(info_span!("test::thread", thread_id = 0), || Err("failed")),
+            Err("failed")
+        );
+    }
+
+    #[test]
+    fn should_emit_events_in_spans() {
+        let recording = Arc::new(Recording::default());
+        ::tracing::subscriber::with_default(Recorder(recording.clone()), || {
+            run_traced(info_span!("test::thread", thread_id = 0), || {
+                let mut iter = TracedIterator::new(
+                    fallible_iterator::convert(vec![Ok(1), Ok(2)].into_iter()),
+                    info_span!("test::inline", thread_id = 0),
+                );
+                while iter.next()?.is_some() {}
+                Ok::<_, &str>(())
+            })
+            .unwrap();
+            run_traced(info_span!("test::failing", thread_id = 1), || Err("boom")).unwrap_err();
+        });
+        assert_eq!(
+            *recording.events.lock().unwrap(),
+            [
+                "test::thread: thread start",
+                "test::inline: stream start",
+                "test::inline: end of stream records=2",
+                "test::thread: thread end",
+                "test::failing: thread start",
+                "test::failing: thread failed error=boom",
+            ]
+        );
+    }
+}
@@ -1,27 +1,87 @@
-use std::io::Seek;
+use std::{
+    fs::File,
+    io::{BufReader, Cursor, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
 
 use bincode::DefaultOptions;
 use serde::{de::Error, Deserialize, Serialize, Serializer};
-use tempfile::{spooled_tempfile, SpooledTempFile};
 
 const DEFAULT_MAX_SIZE_IN_MEMORY: usize = 4096;
 
 const THIS_IS_THE_END: u64 = 0x_74_68_65_20_65_6e_64_21;
 
+/// Spill files are short-lived, speed matters more than ratio.
+const ZSTD_LEVEL: i32 = 1;
+
+/// The stream compression of a [`Buffer`].
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Debug, Default)]
+pub enum BufferCompression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+/// Options of a [`Buffer`].
+#[derive(Clone, Debug)]
+pub struct BufferOptions {
+    /// The size in bytes above which the buffer is spilled to a temporary file.
+    ///
+    /// When the buffer is compressed, this is the compressed size.
+    pub max_size_in_memory: usize,
+    /// The directory of the temporary file, the system temporary directory if not set.
+    pub spill_dir: Option<PathBuf>,
+    /// The compression of the serialized records, both in memory and in the temporary file, none
+    /// by default.
+    pub compression: BufferCompression,
+}
+
+impl Default for BufferOptions {
+    fn default() -> Self {
+        Self {
+            max_size_in_memory: DEFAULT_MAX_SIZE_IN_MEMORY,
+            spill_dir: None,
+            compression: BufferCompression::None,
+        }
+    }
+}
+
 pub struct Buffer {
-    file: SpooledTempFile,
+    writer: BufferWriter,
     options: DefaultOptions,
 }
 
 impl Buffer {
     pub fn new() -> Self {
-        Self::with_max_size_in_memory(DEFAULT_MAX_SIZE_IN_MEMORY)
+        Self::uncompressed(&BufferOptions::default())
     }
 
     pub fn with_max_size_in_memory(max_size_in_memory: usize) -> Self {
-        let file = spooled_tempfile(max_size_in_memory);
-        let options = DefaultOptions::new();
-        Self { file, options }
+        Self::uncompressed(&BufferOptions {
+            max_size_in_memory,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_options(options: &BufferOptions) -> Result<Self, bincode::Error> {
+        let file = SpillFile::new(options);
+        let writer = match options.compression {
+            BufferCompression::None => BufferWriter::None(file),
+            BufferCompression::Lz4 => BufferWriter::Lz4(lz4_flex::frame::FrameEncoder::new(file)),
+            BufferCompression::Zstd => BufferWriter::Zstd(zstd::Encoder::new(file, ZSTD_LEVEL)?),
+        };
+        Ok(Self {
+            writer,
+            options: DefaultOptions::new(),
+        })
+    }
+
+    fn uncompressed(options: &BufferOptions) -> Self {
+        Self {
+            writer: BufferWriter::None(SpillFile::new(options)),
+            options: DefaultOptions::new(),
+        }
     }
 
     pub fn push<Data: Serialize>(&mut self, data: Data) -> Result<(), bincode::Error> {
@@ -32,14 +92,13 @@ impl Buffer {
     pub fn end_writing(mut self) -> Result<BufferReader, bincode::Error> {
         let mut serializer = self.serializer();
         serializer.serialize_u64(THIS_IS_THE_END)?;
-        self.file.seek(std::io::SeekFrom::Start(0))?;
         Ok(BufferReader {
-            deserializer: bincode::Deserializer::with_reader(self.file, self.options),
+            deserializer: bincode::Deserializer::with_reader(self.writer.finish()?, self.options),
         })
     }
 
-    fn serializer(&mut self) -> bincode::Serializer<&mut SpooledTempFile, &mut DefaultOptions> {
-        bincode::Serializer::new(&mut self.file, &mut self.options)
+    fn serializer(&mut self) -> bincode::Serializer<&mut BufferWriter, &mut DefaultOptions> {
+        bincode::Serializer::new(&mut self.writer, &mut self.options)
     }
 }
 
@@ -49,9 +108,147 @@ impl Default for Buffer {
     }
 }
 
+/// A temporary file kept in memory until it grows above `max_size`.
+enum SpillFile {
+    Memory {
+        cursor: Cursor<Vec<u8>>,
+        max_size: usize,
+        spill_dir: Option<PathBuf>,
+    },
+    File(File),
+}
+
+impl SpillFile {
+    fn new(options: &BufferOptions) -> Self {
+        SpillFile::Memory {
+            cursor: Cursor::new(Vec::new()),
+            max_size: options.max_size_in_memory,
+            spill_dir: options.spill_dir.clone(),
+        }
+    }
+
+    fn spill(&mut self) -> std::io::Result<()> {
+        if let SpillFile::Memory {
+            cursor, spill_dir, ..
+        } = self
+        {
+            let mut file = if let Some(spill_dir) = spill_dir {
+                tempfile::tempfile_in(spill_dir)?
+            } else {
+                tempfile::tempfile()?
+            };
+            file.write_all(cursor.get_ref())?;
+            file.seek(SeekFrom::Start(cursor.position()))?;
+            *self = SpillFile::File(file);
+        }
+        Ok(())
+    }
+}
+
+impl Write for SpillFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let SpillFile::Memory {
+            cursor, max_size, ..
+        } = self
+        {
+            if cursor.position() as usize + buf.len() > *max_size {
+                self.spill()?;
+            }
+        }
+        match self {
+            SpillFile::Memory { cursor, .. } => cursor.write(buf),
+            SpillFile::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            SpillFile::Memory { .. } => Ok(()),
+            SpillFile::File(file) => file.flush(),
+        }
+    }
+}
+
+impl Read for SpillFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SpillFile::Memory { cursor, .. } => cursor.read(buf),
+            SpillFile::File(file) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for SpillFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            SpillFile::Memory { cursor, .. } => cursor.seek(pos),
+            SpillFile::File(file) => file.seek(pos),
+        }
+    }
+}
+
+enum BufferWriter {
+    None(SpillFile),
+    Lz4(lz4_flex::frame::FrameEncoder<SpillFile>),
+    Zstd(zstd::Encoder<'static, SpillFile>),
+}
+
+impl BufferWriter {
+    /// Terminates the compressed stream and rewinds the file for reading.
+    fn finish(self) -> std::io::Result<BufferRead> {
+        Ok(match self {
+            BufferWriter::None(file) => BufferRead::None(rewind(file)?),
+            BufferWriter::Lz4(encoder) => BufferRead::Lz4(lz4_flex::frame::FrameDecoder::new(
+                rewind(encoder.finish()?)?,
+            )),
+            BufferWriter::Zstd(encoder) => {
+                BufferRead::Zstd(zstd::Decoder::new(rewind(encoder.finish()?)?)?)
+            }
+        })
+    }
+}
+
+fn rewind(mut file: SpillFile) -> std::io::Result<SpillFile> {
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+impl Write for BufferWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            BufferWriter::None(file) => file.write(buf),
+            BufferWriter::Lz4(encoder) => encoder.write(buf),
+            BufferWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            BufferWriter::None(file) => file.flush(),
+            BufferWriter::Lz4(encoder) => encoder.flush(),
+            BufferWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+enum BufferRead {
+    None(SpillFile),
+    Lz4(lz4_flex::frame::FrameDecoder<SpillFile>),
+    Zstd(zstd::Decoder<'static, BufReader<SpillFile>>),
+}
+
+impl Read for BufferRead {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            BufferRead::None(file) => file.read(buf),
+            BufferRead::Lz4(decoder) => decoder.read(buf),
+            BufferRead::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
 pub struct BufferReader {
-    deserializer:
-        bincode::Deserializer<bincode::de::read::IoReader<SpooledTempFile>, DefaultOptions>,
+    deserializer: bincode::Deserializer<bincode::de::read::IoReader<BufferRead>, DefaultOptions>,
 }
 
 impl BufferReader {
@@ -90,3 +287,61 @@ fn test_rolled() {
     assert_eq!(reader.read::<i32>().unwrap(), 42);
     reader.end_reading().unwrap();
 }
+
+#[test]
+fn test_spill_dir() {
+    let spill_dir = tempfile::tempdir().unwrap();
+    let mut buffer = Buffer::with_options(&BufferOptions {
+        max_size_in_memory: 1,
+        spill_dir: Some(spill_dir.path().to_path_buf()),
+        ..Default::default()
+    })
+    .unwrap();
+    buffer.push("universe").unwrap();
+    buffer.push(42).unwrap();
+    assert!(matches!(
+        buffer.writer,
+        BufferWriter::None(SpillFile::File(_))
+    ));
+    let mut reader = buffer.end_writing().unwrap();
+    assert_eq!(reader.read::<String>().unwrap(), "universe");
+    assert_eq!(reader.read::<i32>().unwrap(), 42);
+    reader.end_reading().unwrap();
+}
+
+#[cfg(test)]
+#[rstest::rstest]
+fn test_compressed(
+    #[values(BufferCompression::Lz4, BufferCompression::Zstd)] compression: BufferCompression,
+    #[values(1, 1024 * 1024)] max_size_in_memory: usize,
+) {
+    let mut buffer = Buffer::with_options(&BufferOptions {
+        max_size_in_memory,
+        compression,
+        ..Default::default()
+    })
+    .unwrap();
+    for i in 0..1000 {
+        buffer.push("universe").unwrap();
+        buffer.push(i).unwrap();
+    }
+    let mut reader = buffer.end_writing().unwrap();
+    for i in 0..1000 {
+        assert_eq!(reader.read::<String>().unwrap(), "universe");
+        assert_eq!(reader.read::<i32>().unwrap(), i);
+    }
+    reader.end_reading().unwrap();
+}
+
+#[test]
+fn test_compressed_end_tag() {
+    let mut buffer = Buffer::with_options(&BufferOptions {
+        compression: BufferCompression::Lz4,
+        ..Default::default()
+    })
+    .unwrap();
+    buffer.push(42_u64).unwrap();
+    let mut reader = buffer.end_writing().unwrap();
+    // Skipping the record reads it as the end tag
+    assert!(reader.end_reading().is_err());
+}
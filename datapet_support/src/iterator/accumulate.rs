@@ -3,7 +3,7 @@ use std::marker::PhantomData;
 use fallible_iterator::FallibleIterator;
 use serde::{Deserialize, Serialize};
 
-use crate::data::buffer::{Buffer, BufferReader};
+use crate::data::buffer::{Buffer, BufferOptions, BufferReader};
 
 /// Accumulates items in memory and stream them.
 #[derive(new)]
@@ -14,11 +14,26 @@ pub struct Accumulate<
     E,
 > {
     input: I,
+    #[new(default)]
+    options: BufferOptions,
     #[new(value = "State::Buffering")]
     state: State,
     _de: PhantomData<&'de ()>,
 }
 
+impl<'de, I: FallibleIterator<Item = R, Error = E>, R: Serialize + Deserialize<'de>, E>
+    Accumulate<'de, I, R, E>
+{
+    pub fn with_options(input: I, options: BufferOptions) -> Self {
+        Self {
+            input,
+            options,
+            state: State::Buffering,
+            _de: PhantomData,
+        }
+    }
+}
+
 impl<
         'de,
         I: FallibleIterator<Item = R, Error = E>,
@@ -31,7 +46,7 @@ impl<
 
     fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
         if let State::Buffering = &self.state {
-            let mut buffer = Buffer::new();
+            let mut buffer = Buffer::with_options(&self.options)?;
             let mut written = 0;
             while let Some(record) = self.input.next()? {
                 buffer.push(record)?;
@@ -127,3 +142,33 @@ fn should_accumulate_stream() {
     assert_matches!(stream.next(), Ok(None));
     assert_matches!(stream.next(), Ok(None));
 }
+
+#[test]
+fn should_accumulate_compressed_stream() {
+    use crate::data::buffer::BufferCompression;
+
+    #[derive(Debug)]
+    enum Error {
+        Bincode(bincode::Error),
+    }
+
+    impl From<bincode::Error> for Error {
+        fn from(err: bincode::Error) -> Self {
+            Self::Bincode(err)
+        }
+    }
+
+    let mut stream = Accumulate::with_options(
+        fallible_iterator::convert((0..10_000).map(Ok::<u32, Error>)),
+        BufferOptions {
+            max_size_in_memory: 1,
+            compression: BufferCompression::Zstd,
+            ..Default::default()
+        },
+    );
+    for i in 0..10_000 {
+        assert_matches!(stream.next(), Ok(Some(v)) if v == i);
+    }
+    // End of stream
+    assert_matches!(stream.next(), Ok(None));
+}
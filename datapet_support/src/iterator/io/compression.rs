@@ -0,0 +1,192 @@
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Debug)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression from the file name extension, `None` when it is not a known
+    /// compression extension.
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Detects the compression from the first bytes of a file.
+    pub fn from_magic_bytes(header: &[u8]) -> Self {
+        if header.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Opens a file for reading, decompressing it on the fly.
+///
+/// When `compression` is not specified, it is detected from the magic bytes of the file.
+pub fn open<P: AsRef<Path>>(
+    path: P,
+    compression: Option<Compression>,
+) -> Result<Box<dyn BufRead + Send>, std::io::Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let compression = match compression {
+        Some(compression) => compression,
+        None => Compression::from_magic_bytes(reader.fill_buf()?),
+    };
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(
+            reader,
+        ))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// Writer returned by [`create`].
+///
+/// [`CompressedWriter::finish`] must be called in order to properly terminate the compressed
+/// stream and catch any final write error.
+pub enum CompressedWriter {
+    None(BufWriter<File>),
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl CompressedWriter {
+    pub fn finish(self) -> Result<(), std::io::Error> {
+        let mut inner = match self {
+            CompressedWriter::None(inner) => inner,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        inner.flush()
+    }
+}
+
+impl Write for CompressedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            CompressedWriter::None(inner) => inner.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            CompressedWriter::None(inner) => inner.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Creates a file for writing, compressing it on the fly.
+///
+/// When `compression` is not specified, it is detected from the extension of the file name.
+pub fn create<P: AsRef<Path>>(
+    path: P,
+    compression: Option<Compression>,
+) -> Result<CompressedWriter, std::io::Error> {
+    let compression = compression
+        .or_else(|| Compression::from_extension(&path))
+        .unwrap_or(Compression::None);
+    let writer = BufWriter::new(File::create(path)?);
+    Ok(match compression {
+        Compression::None => CompressedWriter::None(writer),
+        Compression::Gzip => CompressedWriter::Gzip(flate2::write::GzEncoder::new(
+            writer,
+            flate2::Compression::default(),
+        )),
+        Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use fallible_iterator::FallibleIterator;
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::iterator::io::buf::ReadLines;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/io")
+            .join(name)
+    }
+
+    fn read_all_lines(input: Box<dyn BufRead + Send>) -> Vec<Box<str>> {
+        ReadLines::new(input).collect().unwrap()
+    }
+
+    #[rstest]
+    #[case("lines.txt", None)]
+    #[case("lines.txt.gz", None)]
+    #[case("lines.txt.zst", None)]
+    #[case("lines.gz.dat", None)]
+    #[case("lines.gz.dat", Some(Compression::Gzip))]
+    #[case("lines.txt.zst", Some(Compression::Zstd))]
+    fn should_read_compressed_fixture(
+        #[case] name: &str,
+        #[case] compression: Option<Compression>,
+    ) {
+        let lines = read_all_lines(open(fixture(name), compression).unwrap());
+        assert_eq!(
+            lines,
+            ["Hello", "Compressed", "World"]
+                .into_iter()
+                .map(Into::into)
+                .collect::<Vec<Box<str>>>()
+        );
+    }
+
+    #[test]
+    fn should_not_decompress_when_explicitly_disabled() {
+        let mut input = open(fixture("lines.txt.gz"), Some(Compression::None)).unwrap();
+        assert_eq!(input.fill_buf().unwrap()[..2], GZIP_MAGIC);
+    }
+
+    #[rstest]
+    #[case("out.txt", None, Compression::None)]
+    #[case("out.txt.gz", None, Compression::Gzip)]
+    #[case("out.txt.zst", None, Compression::Zstd)]
+    #[case("out.dat", Some(Compression::Zstd), Compression::Zstd)]
+    fn should_write_and_read_back(
+        #[case] name: &str,
+        #[case] compression: Option<Compression>,
+        #[case] expected_compression: Compression,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+
+        let mut output = create(&path, compression).unwrap();
+        writeln!(output, "Hello").unwrap();
+        writeln!(output, "World").unwrap();
+        output.finish().unwrap();
+
+        let header = std::fs::read(&path).unwrap();
+        assert_eq!(Compression::from_magic_bytes(&header), expected_compression);
+
+        let lines = read_all_lines(open(&path, None).unwrap());
+        assert_eq!(lines, vec![Box::<str>::from("Hello"), "World".into()]);
+    }
+}
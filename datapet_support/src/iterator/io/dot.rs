@@ -0,0 +1,91 @@
+use std::{fmt::Display, io::Write};
+
+/// Writes a Graphviz directed graph.
+///
+/// Node ids are made of a prefix, which is used as a namespace, and of any displayable value such
+/// as an [`AnchorId`](crate::AnchorId). Ids and labels are always quoted.
+pub struct DotWriter<W: Write> {
+    output: W,
+}
+
+impl<W: Write> DotWriter<W> {
+    pub fn new(mut output: W, name: &str) -> Result<Self, std::io::Error> {
+        writeln!(output, "digraph {} {{", quote(name))?;
+        Ok(Self { output })
+    }
+
+    pub fn node(
+        &mut self,
+        prefix: &str,
+        id: &dyn Display,
+        label: Option<&dyn Display>,
+    ) -> Result<(), std::io::Error> {
+        let id = quote(&format!("{}{}", prefix, id));
+        if let Some(label) = label {
+            writeln!(self.output, "{} [label={}]", id, quote(&label.to_string()))
+        } else {
+            writeln!(self.output, "{}", id)
+        }
+    }
+
+    pub fn edge(
+        &mut self,
+        from_prefix: &str,
+        from: &dyn Display,
+        to_prefix: &str,
+        to: &dyn Display,
+    ) -> Result<(), std::io::Error> {
+        writeln!(
+            self.output,
+            "{} -> {}",
+            quote(&format!("{}{}", from_prefix, from)),
+            quote(&format!("{}{}", to_prefix, to))
+        )
+    }
+
+    pub fn finish(mut self) -> Result<W, std::io::Error> {
+        writeln!(self.output, "}}")?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[test]
+fn should_write_dot_graph() {
+    use crate::AnchorId;
+
+    let mut writer = DotWriter::new(Vec::new(), "test").unwrap();
+    writer
+        .node("word_", &AnchorId::<0>::new(1), Some(&"say \"hello\""))
+        .unwrap();
+    writer.node("word_", &AnchorId::<0>::new(2), None).unwrap();
+    writer
+        .edge("word_", &AnchorId::<0>::new(1), "word_", &AnchorId::<0>::new(2))
+        .unwrap();
+    let output = writer.finish().unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        concat!(
+            "digraph \"test\" {\n",
+            "\"word_1\" [label=\"say \\\"hello\\\"\"]\n",
+            "\"word_2\"\n",
+            "\"word_1\" -> \"word_2\"\n",
+            "}\n"
+        )
+    );
+}
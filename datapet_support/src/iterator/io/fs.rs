@@ -0,0 +1,245 @@
+use fallible_iterator::FallibleIterator;
+use glob::MatchOptions;
+use std::{
+    fs::{DirEntry, Metadata},
+    io::Read,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+pub use glob::Pattern;
+
+use super::compression::{self, Compression};
+
+/// A file found by [`ListFiles`].
+#[derive(Debug)]
+pub struct FileEntry {
+    /// The path, relative to the root, with `/` separators.
+    pub path: Box<str>,
+    /// The full path, usable to open the file.
+    pub full_path: PathBuf,
+    pub size: u64,
+    /// Modification time, in seconds since the UNIX epoch.
+    pub modified: u64,
+}
+
+impl FileEntry {
+    /// Reads the content of the file, decompressing it on the fly.
+    ///
+    /// When `compression` is not specified, it is detected from the magic bytes of the file.
+    pub fn read_content(
+        &self,
+        compression: Option<Compression>,
+    ) -> Result<Box<str>, std::io::Error> {
+        let mut bytes = Vec::new();
+        compression::open(&self.full_path, compression)?.read_to_end(&mut bytes)?;
+        Ok(String::from_utf8_lossy(&bytes).into())
+    }
+}
+
+struct Directory {
+    relative_path: String,
+    /// The canonical path, only when following symlinks.
+    canonical_path: Option<PathBuf>,
+    // Entries sorted in reverse order, so that popping gives the smallest one
+    entries: Vec<(String, DirEntry)>,
+}
+
+/// Walks a directory tree and streams the regular files whose relative path matches a glob
+/// pattern.
+///
+/// Files are streamed in lexicographic order of their relative path: each directory is listed
+/// and sorted with sub-directories keyed by their name followed by `/`, which is exactly where
+/// their files will sort in the complete path order.
+///
+/// When following symlinks, a directory is listed under every path reaching it, except when it
+/// is one of its own ancestors, in which case the loop is not followed.
+pub struct ListFiles {
+    pattern: Pattern,
+    follow_symlinks: bool,
+    stack: Vec<Directory>,
+    root: Option<PathBuf>,
+}
+
+impl ListFiles {
+    pub fn new<P: AsRef<Path>>(root: P, pattern: Pattern, follow_symlinks: bool) -> Self {
+        Self {
+            pattern,
+            follow_symlinks,
+            stack: Vec::new(),
+            root: Some(root.as_ref().to_path_buf()),
+        }
+    }
+
+    fn push_directory(&mut self, path: &Path, relative_path: String) -> Result<(), std::io::Error> {
+        let canonical_path = if self.follow_symlinks {
+            let canonical_path = path.canonicalize()?;
+            if self
+                .stack
+                .iter()
+                .any(|directory| directory.canonical_path.as_ref() == Some(&canonical_path))
+            {
+                // Linked to one of its ancestors, break the loop
+                return Ok(());
+            }
+            Some(canonical_path)
+        } else {
+            None
+        };
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let mut key = entry.file_name().to_string_lossy().into_owned();
+            if self.metadata(&entry)?.is_dir() {
+                key.push('/');
+            }
+            entries.push((key, entry));
+        }
+        entries.sort_by(|a, b| b.0.cmp(&a.0));
+        self.stack.push(Directory {
+            relative_path,
+            canonical_path,
+            entries,
+        });
+        Ok(())
+    }
+
+    fn metadata(&self, entry: &DirEntry) -> Result<Metadata, std::io::Error> {
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() && self.follow_symlinks {
+            std::fs::metadata(entry.path())
+        } else {
+            entry.metadata()
+        }
+    }
+}
+
+impl FallibleIterator for ListFiles {
+    type Item = FileEntry;
+    type Error = std::io::Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(root) = self.root.take() {
+            self.push_directory(&root, String::new())?;
+        }
+        let match_options = MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        loop {
+            let Some(directory) = self.stack.last_mut() else {
+                return Ok(None);
+            };
+            let Some((key, entry)) = directory.entries.pop() else {
+                self.stack.pop();
+                continue;
+            };
+            let relative_path = format!("{}{}", directory.relative_path, key);
+            let file_type = entry.file_type()?;
+            if file_type.is_symlink() && !self.follow_symlinks {
+                continue;
+            }
+            let metadata = self.metadata(&entry)?;
+            if metadata.is_dir() {
+                self.push_directory(&entry.path(), relative_path)?;
+            } else if metadata.is_file() && self.pattern.matches_with(&relative_path, match_options)
+            {
+                let modified = metadata
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default();
+                return Ok(Some(FileEntry {
+                    path: relative_path.into(),
+                    full_path: entry.path(),
+                    size: metadata.len(),
+                    modified,
+                }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn should_list_files_in_path_order() {
+        let root = tempfile::tempdir().unwrap();
+        touch(root.path(), "b.txt", "b");
+        touch(root.path(), "a/z.txt", "az");
+        touch(root.path(), "a.txt", "a");
+        touch(root.path(), "a-b/c.txt", "abc");
+        touch(root.path(), "a/y.rs", "ay");
+
+        let mut stream = ListFiles::new(root.path(), Pattern::new("**/*.txt").unwrap(), false);
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "a-b/c.txt" && file.size == 3);
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "a.txt");
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "a/z.txt");
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "b.txt" && file.read_content(None).unwrap().as_ref() == "b");
+        // End of stream
+        assert_matches!(stream.next(), Ok(None));
+        assert_matches!(stream.next(), Ok(None));
+    }
+
+    #[test]
+    fn should_match_pattern_relative_to_root() {
+        let root = tempfile::tempdir().unwrap();
+        touch(root.path(), "a.txt", "a");
+        touch(root.path(), "a/b.txt", "ab");
+
+        let mut stream = ListFiles::new(root.path(), Pattern::new("*.txt").unwrap(), false);
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "a.txt");
+        assert_matches!(stream.next(), Ok(None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_follow_symlinks_without_looping() {
+        let root = tempfile::tempdir().unwrap();
+        touch(root.path(), "a/b.txt", "ab");
+        std::os::unix::fs::symlink(root.path().join("a"), root.path().join("a/loop")).unwrap();
+        std::os::unix::fs::symlink(root.path().join("a/b.txt"), root.path().join("c.txt")).unwrap();
+
+        let mut stream = ListFiles::new(root.path(), Pattern::new("**/*.txt").unwrap(), false);
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "a/b.txt");
+        assert_matches!(stream.next(), Ok(None));
+
+        let mut stream = ListFiles::new(root.path(), Pattern::new("**/*.txt").unwrap(), true);
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "a/b.txt");
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "c.txt");
+        assert_matches!(stream.next(), Ok(None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn should_list_linked_directory_under_each_path() {
+        let root = tempfile::tempdir().unwrap();
+        touch(root.path(), "b/c.txt", "bc");
+        std::os::unix::fs::symlink(root.path().join("b"), root.path().join("a")).unwrap();
+
+        let mut stream = ListFiles::new(root.path(), Pattern::new("**/*.txt").unwrap(), true);
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "a/c.txt");
+        assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == "b/c.txt");
+        assert_matches!(stream.next(), Ok(None));
+    }
+
+    #[test]
+    fn should_read_compressed_content() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/io");
+
+        let mut stream = ListFiles::new(&root, Pattern::new("lines.txt*").unwrap(), false);
+        for name in ["lines.txt", "lines.txt.gz", "lines.txt.zst"] {
+            assert_matches!(stream.next(), Ok(Some(file)) if &*file.path == name
+                && file.read_content(None).unwrap().as_ref() == "Hello\nCompressed\nWorld\n");
+        }
+        assert_matches!(stream.next(), Ok(None));
+    }
+}
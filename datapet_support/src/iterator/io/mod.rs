@@ -1 +1,4 @@
 pub mod buf;
+pub mod compression;
+pub mod dot;
+pub mod fs;
@@ -5,6 +5,7 @@ pub mod collections;
 pub mod dedup;
 pub mod group;
 pub mod io;
+pub mod parallel;
 pub mod sort;
 pub mod sync;
 
@@ -0,0 +1,281 @@
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc::sync_channel, Arc, Mutex},
+};
+
+use fallible_iterator::FallibleIterator;
+
+use crate::chain::cancellation::Cancellation;
+
+/// Maps the records of `input` on `workers` threads and sends them with `send`.
+///
+/// In ordered mode, the records are sent in the order of the input, otherwise as soon as they are
+/// mapped. At most `workers * 2` records are then mapped ahead of the next record to send, which
+/// bounds the records waiting for their turn. The first error, whether it comes from the input, a
+/// worker or `send`, stops the processing and is returned. The workers also stop once
+/// `cancellation` is cancelled.
+///
+/// Each worker runs its loop through `run_worker`, with its index, e.g. in the tracing span or
+/// the allocation group of the node.
+pub fn parallel_map<I, R, E, F, S, W>(
+    input: I,
+    workers: usize,
+    ordered: bool,
+    cancellation: &Cancellation<E>,
+    run_worker: W,
+    map: F,
+    mut send: S,
+) -> Result<(), E>
+where
+    I: FallibleIterator<Item = R, Error = E> + Send,
+    R: Send,
+    E: Send,
+    F: Fn(&mut R) -> Result<(), E> + Sync,
+    S: FnMut(R) -> Result<(), E>,
+    W: Fn(usize, &mut dyn FnMut()) + Sync,
+{
+    let workers = workers.max(1);
+    let window = workers * 2;
+    std::thread::scope(|scope| {
+        let (work_tx, work_rx) = sync_channel::<(usize, R)>(window);
+        let (result_tx, result_rx) = sync_channel::<(usize, Result<R, E>)>(window);
+        // In ordered mode, the feeder takes a credit for each record and gets it back once the
+        // record is sent, so that it never feeds beyond the reorder window
+        let (credit_tx, credit_rx) = if ordered {
+            let (credit_tx, credit_rx) = sync_channel::<()>(window);
+            for _ in 0..window {
+                credit_tx.send(()).expect("credit");
+            }
+            (Some(credit_tx), Some(credit_rx))
+        } else {
+            (None, None)
+        };
+
+        let feeder = scope.spawn(move || -> Result<(), E> {
+            let mut input = input;
+            let mut index = 0;
+            while let Some(record) = input.next()? {
+                if let Some(credit_rx) = &credit_rx {
+                    if credit_rx.recv().is_err() {
+                        // The processing stopped on an error
+                        break;
+                    }
+                }
+                if cancellation.is_cancelled() || work_tx.send((index, record)).is_err() {
+                    // The chain or the processing stopped
+                    break;
+                }
+                index += 1;
+            }
+            Ok(())
+        });
+
+        // The workers own the receiver, so that the feeder stops once they all stopped
+        let work_rx = Arc::new(Mutex::new(work_rx));
+        for worker in 0..workers {
+            let work_rx = work_rx.clone();
+            let result_tx = result_tx.clone();
+            let map = &map;
+            let run_worker = &run_worker;
+            scope.spawn(move || {
+                run_worker(worker, &mut || loop {
+                    if cancellation.is_cancelled() {
+                        break;
+                    }
+                    let work = work_rx.lock().unwrap_or_else(|err| err.into_inner()).recv();
+                    let (index, mut record) = match work {
+                        Ok(work) => work,
+                        Err(_) => break,
+                    };
+                    let result = map(&mut record).map(|()| record);
+                    if result_tx.send((index, result)).is_err() {
+                        break;
+                    }
+                })
+            });
+        }
+        drop(work_rx);
+        drop(result_tx);
+
+        let mut pending = BTreeMap::<usize, R>::new();
+        let mut next_index = 0;
+        for (index, result) in result_rx {
+            let record = result?;
+            if let Some(credit_tx) = &credit_tx {
+                pending.insert(index, record);
+                while let Some(record) = pending.remove(&next_index) {
+                    send(record)?;
+                    next_index += 1;
+                    // The feeder may be done with the input already
+                    credit_tx.send(()).ok();
+                }
+            } else {
+                send(record)?;
+            }
+        }
+
+        feeder.join().expect("feeder")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::parallel_map;
+    use crate::chain::cancellation::Cancellation;
+    use fallible_iterator::convert;
+
+    fn run(_worker: usize, work: &mut dyn FnMut()) {
+        work()
+    }
+
+    #[test]
+    fn should_map_in_order() {
+        let input = convert((0..1000).map(Ok::<u32, String>));
+        let mut output = Vec::new();
+        parallel_map(
+            input,
+            4,
+            true,
+            &Cancellation::new(),
+            run,
+            |record| {
+                *record *= 2;
+                Ok(())
+            },
+            |record| {
+                output.push(record);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(output, (0..1000).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_map_unordered() {
+        let input = convert((0..1000).map(Ok::<u32, String>));
+        let mut output = Vec::new();
+        parallel_map(
+            input,
+            4,
+            false,
+            &Cancellation::new(),
+            run,
+            |record| {
+                *record += 1;
+                Ok(())
+            },
+            |record| {
+                output.push(record);
+                Ok(())
+            },
+        )
+        .unwrap();
+        output.sort_unstable();
+        assert_eq!(output, (1..=1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn should_stop_on_error() {
+        let input = convert((0..1000).map(Ok::<u32, String>));
+        let result = parallel_map(
+            input,
+            4,
+            true,
+            &Cancellation::new(),
+            run,
+            |record| {
+                if *record == 500 {
+                    Err("bad record".to_string())
+                } else {
+                    Ok(())
+                }
+            },
+            |_| Ok(()),
+        );
+        assert_eq!(result, Err("bad record".to_string()));
+    }
+
+    #[test]
+    fn should_return_input_error() {
+        let input = convert((0..10).map(|i| {
+            if i < 5 {
+                Ok(i)
+            } else {
+                Err("bad input".to_string())
+            }
+        }));
+        let mut count = 0;
+        let result = parallel_map(
+            input,
+            2,
+            true,
+            &Cancellation::new(),
+            run,
+            |_: &mut u32| Ok(()),
+            |_| {
+                count += 1;
+                Ok(())
+            },
+        );
+        assert_eq!(result, Err("bad input".to_string()));
+        assert_eq!(count, 5);
+    }
+
+    #[test]
+    fn should_bound_reorder_window() {
+        let read = AtomicUsize::new(0);
+        let input = convert((0..1000).map(|i| {
+            read.fetch_add(1, Ordering::SeqCst);
+            Ok::<u32, String>(i)
+        }));
+        let mut read_at_first_send = None;
+        parallel_map(
+            input,
+            4,
+            true,
+            &Cancellation::new(),
+            run,
+            |record| {
+                if *record == 0 {
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+                Ok(())
+            },
+            |_| {
+                read_at_first_send.get_or_insert_with(|| read.load(Ordering::SeqCst));
+                Ok(())
+            },
+        )
+        .unwrap();
+        // The window of 8 records, and one more read which waits for room in it
+        assert!(read_at_first_send.unwrap() <= 9);
+    }
+
+    #[test]
+    fn should_stop_on_cancellation() {
+        let cancellation = Cancellation::<String>::new();
+        cancellation.interrupt();
+        let input = convert((0..1000).map(Ok::<u32, String>));
+        let mut count = 0;
+        parallel_map(
+            input,
+            4,
+            false,
+            &cancellation,
+            run,
+            |_| Ok(()),
+            |_| {
+                count += 1;
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -1,6 +1,15 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+};
 
 use binary_heap_plus::BinaryHeap;
+use bincode::{DefaultOptions, Options};
 use compare::Compare;
 use fallible_iterator::FallibleIterator;
 #[cfg(test)]
@@ -8,10 +17,78 @@ use rstest::rstest;
 use serde::{Deserialize, Serialize};
 
 use super::collections::CollectionsIteratorFnHelper;
-use crate::data::buffer::{Buffer, BufferReader};
+#[cfg(test)]
+use crate::data::buffer::BufferCompression;
+use crate::data::buffer::{Buffer, BufferOptions, BufferReader};
 
 pub const DEFAULT_SLICE_SIZE: usize = 1 << 16;
 
+/// Options of [`Sort`] and [`ParallelSort`].
+#[derive(Clone, Debug)]
+pub struct SortOptions {
+    /// The maximum number of records of a slice sorted in memory.
+    pub slice_size: usize,
+    /// The maximum size in bytes of the records sorted in memory, estimated from their serialized
+    /// size. When set, slices are bounded by this budget instead of `slice_size`.
+    pub memory_budget: Option<usize>,
+    /// The options of the buffers the sorted slices are spilled to.
+    pub buffer: BufferOptions,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        Self {
+            slice_size: DEFAULT_SLICE_SIZE,
+            memory_budget: None,
+            buffer: BufferOptions::default(),
+        }
+    }
+}
+
+/// Tells when the slice being filled is full.
+struct Slicer {
+    slice_size: usize,
+    memory_budget: Option<usize>,
+    len: usize,
+    size: usize,
+}
+
+impl Slicer {
+    fn new(options: &SortOptions, slices_in_memory: usize) -> Self {
+        Self {
+            slice_size: options.slice_size,
+            memory_budget: options
+                .memory_budget
+                .map(|memory_budget| (memory_budget / slices_in_memory).max(1)),
+            len: 0,
+            size: 0,
+        }
+    }
+
+    /// Accounts for `record`, returns `true` if the current slice must be sorted before `record`
+    /// is added to a new one.
+    fn push<Record: Serialize>(&mut self, record: &Record) -> Result<bool, bincode::Error> {
+        let full = if let Some(memory_budget) = self.memory_budget {
+            let size = DefaultOptions::new().serialized_size(record)? as usize;
+            let full = self.len > 0 && self.size + size > memory_budget;
+            if full {
+                self.len = 0;
+                self.size = 0;
+            }
+            self.size += size;
+            full
+        } else {
+            let full = self.len >= self.slice_size;
+            if full {
+                self.len = 0;
+            }
+            full
+        };
+        self.len += 1;
+        Ok(full)
+    }
+}
+
 /// Sorts items in memory and stream them.
 pub struct Sort<Input: FallibleIterator<Item = Record, Error = Error>, Record, Error, CmpFn>
 where
@@ -19,7 +96,7 @@ where
 {
     input: Input,
     cmp: CmpFn,
-    slice_size: usize,
+    options: SortOptions,
     state: State<Record, CmpFn>,
 }
 
@@ -29,14 +106,25 @@ where
     CmpFn: Fn(&Record, &Record) -> Ordering,
 {
     pub fn new(input: Input, cmp: CmpFn) -> Self {
-        Self::with_slice_size(input, cmp, DEFAULT_SLICE_SIZE)
+        Self::with_options(input, cmp, SortOptions::default())
     }
 
     pub fn with_slice_size(input: Input, cmp: CmpFn, slice_size: usize) -> Self {
+        Self::with_options(
+            input,
+            cmp,
+            SortOptions {
+                slice_size,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn with_options(input: Input, cmp: CmpFn, options: SortOptions) -> Self {
         Self {
             input,
             cmp,
-            slice_size,
+            options,
             state: State::Buffering,
         }
     }
@@ -56,75 +144,297 @@ where
         if let State::Buffering = &self.state {
             let mut slice_buffers = Vec::new();
             let mut current_slice = Vec::new();
+            let mut slicer = Slicer::new(&self.options, 1);
             while let Some(record) = self.input.next()? {
-                if current_slice.len() >= self.slice_size {
-                    current_slice.sort_by(|r1, r2| (self.cmp)(r1, r2));
-                    let mut buffer = Buffer::new();
-                    let len = current_slice.len();
-                    for record in current_slice {
-                        buffer.push(record)?;
-                    }
-                    current_slice = Vec::new();
-                    slice_buffers.push(SliceBuffer {
-                        buffer: buffer.end_writing()?,
-                        written: len,
-                        read: 0,
+                if slicer.push(&record)? {
+                    slice_buffers.push(sort_slice_into_buffer(
+                        std::mem::take(&mut current_slice),
+                        &self.cmp,
+                        &self.options.buffer,
+                    )?);
+                }
+                current_slice.push(record);
+            }
+            self.state = State::start_reading(slice_buffers, current_slice, self.cmp.clone())?;
+        }
+        Ok(self.state.next_sorted()?)
+    }
+}
+
+/// Sorts items in memory and stream them, sorting and spilling the slices on a pool of `threads`
+/// worker threads while the input is being read.
+///
+/// At most `threads` slices are being sorted at the same time, in addition to the one being
+/// filled, so that the memory budget is shared by `threads + 1` slices.
+pub struct ParallelSort<Input: FallibleIterator<Item = Record, Error = Error>, Record, Error, CmpFn>
+where
+    CmpFn: Fn(&Record, &Record) -> Ordering,
+{
+    input: Input,
+    cmp: CmpFn,
+    threads: usize,
+    options: SortOptions,
+    state: State<Record, CmpFn>,
+}
+
+impl<Input: FallibleIterator<Item = Record, Error = Error>, Record, Error, CmpFn>
+    ParallelSort<Input, Record, Error, CmpFn>
+where
+    CmpFn: Fn(&Record, &Record) -> Ordering,
+{
+    pub fn new(input: Input, cmp: CmpFn, threads: usize) -> Self {
+        Self::with_options(input, cmp, threads, SortOptions::default())
+    }
+
+    pub fn with_slice_size(input: Input, cmp: CmpFn, threads: usize, slice_size: usize) -> Self {
+        Self::with_options(
+            input,
+            cmp,
+            threads,
+            SortOptions {
+                slice_size,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn with_options(input: Input, cmp: CmpFn, threads: usize, options: SortOptions) -> Self {
+        assert!(threads > 0, "threads must be greater than 0");
+        Self {
+            input,
+            cmp,
+            threads,
+            options,
+            state: State::Buffering,
+        }
+    }
+}
+
+impl<'de, Input: FallibleIterator<Item = Record, Error = Error>, Record, Error, CmpFn>
+    FallibleIterator for ParallelSort<Input, Record, Error, CmpFn>
+where
+    CmpFn: Fn(&Record, &Record) -> Ordering + Clone + Send + 'static,
+    Record: Serialize + Deserialize<'de> + Send + 'static,
+    Error: From<bincode::Error>,
+{
+    type Item = Record;
+    type Error = Error;
+
+    fn next(&mut self) -> Result<Option<Self::Item>, Self::Error> {
+        if let State::Buffering = &self.state {
+            let mut pool = None;
+            // Slices are merged in the order of the input, so that the merge is stable as in the
+            // sequential sort
+            let mut slice_buffers = Vec::<Option<SliceBuffer>>::new();
+            let mut in_flight = 0;
+            let mut current_slice = Vec::new();
+            let mut slicer = Slicer::new(&self.options, self.threads + 1);
+            while let Some(record) = self.input.next()? {
+                if slicer.push(&record)? {
+                    let pool = pool.get_or_insert_with(|| {
+                        SortPool::new(self.threads, &self.cmp, &self.options.buffer)
                     });
+                    if in_flight >= self.threads {
+                        pool.receive(&mut slice_buffers)?;
+                        in_flight -= 1;
+                    }
+                    pool.send(slice_buffers.len(), std::mem::take(&mut current_slice));
+                    slice_buffers.push(None);
+                    in_flight += 1;
                 }
                 current_slice.push(record);
             }
-            if !current_slice.is_empty() {
-                // reverse is important
-                current_slice.sort_by(|r1, r2| (self.cmp)(r1, r2).reverse());
+            if let Some(pool) = pool {
+                for _ in 0..in_flight {
+                    pool.receive(&mut slice_buffers)?;
+                }
+                pool.join();
             }
-            let slice_buffers_len = slice_buffers.len();
-            let heads = slice_buffers
-                .iter_mut()
-                .enumerate()
-                .map(
-                    |(
+            let slice_buffers = slice_buffers
+                .into_iter()
+                .map(|slice_buffer| slice_buffer.expect("sorted slice"))
+                .collect();
+            self.state = State::start_reading(slice_buffers, current_slice, self.cmp.clone())?;
+        }
+        Ok(self.state.next_sorted()?)
+    }
+}
+
+type SortedSlice = (
+    usize,
+    std::thread::Result<Result<SliceBuffer, bincode::Error>>,
+);
+
+/// The worker threads of [`ParallelSort`], which sort the slices and spill them to buffers.
+struct SortPool<Record> {
+    work_tx: SyncSender<(usize, Vec<Record>)>,
+    result_rx: Receiver<SortedSlice>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<Record: Serialize + Send + 'static> SortPool<Record> {
+    fn new<CmpFn>(threads: usize, cmp: &CmpFn, buffer_options: &BufferOptions) -> Self
+    where
+        CmpFn: Fn(&Record, &Record) -> Ordering + Clone + Send + 'static,
+    {
+        // At most `threads` slices are in flight, sending them never blocks
+        let (work_tx, work_rx) = sync_channel::<(usize, Vec<Record>)>(threads);
+        let (result_tx, result_rx) = sync_channel::<SortedSlice>(threads);
+        let work_rx = Arc::new(Mutex::new(work_rx));
+        let workers = (0..threads)
+            .map(|_| {
+                let work_rx = work_rx.clone();
+                let result_tx = result_tx.clone();
+                let cmp = cmp.clone();
+                let buffer_options = buffer_options.clone();
+                std::thread::spawn(move || loop {
+                    let work = work_rx.lock().unwrap_or_else(|err| err.into_inner()).recv();
+                    let (index, slice) = match work {
+                        Ok(work) => work,
+                        Err(_) => break,
+                    };
+                    // A panic is resumed by the sort rather than leaving it waiting for the slice
+                    let sorted = catch_unwind(AssertUnwindSafe(|| {
+                        sort_slice_into_buffer(slice, &cmp, &buffer_options)
+                    }));
+                    if result_tx.send((index, sorted)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        Self {
+            work_tx,
+            result_rx,
+            workers,
+        }
+    }
+
+    fn send(&self, index: usize, slice: Vec<Record>) {
+        self.work_tx.send((index, slice)).expect("sort worker");
+    }
+
+    /// Waits for a sorted slice and stores it at its index.
+    fn receive(&self, slice_buffers: &mut [Option<SliceBuffer>]) -> Result<(), bincode::Error> {
+        let (index, sorted) = self.result_rx.recv().expect("sort worker");
+        let slice_buffer = sorted.unwrap_or_else(|payload| std::panic::resume_unwind(payload))?;
+        slice_buffers[index] = Some(slice_buffer);
+        Ok(())
+    }
+
+    fn join(self) {
+        drop(self.work_tx);
+        for worker in self.workers {
+            worker.join().expect("sort worker");
+        }
+    }
+}
+
+fn sort_slice_into_buffer<Record, CmpFn>(
+    mut slice: Vec<Record>,
+    cmp: &CmpFn,
+    buffer_options: &BufferOptions,
+) -> Result<SliceBuffer, bincode::Error>
+where
+    CmpFn: Fn(&Record, &Record) -> Ordering,
+    Record: Serialize,
+{
+    slice.sort_by(|r1, r2| cmp(r1, r2));
+    let mut buffer = Buffer::with_options(buffer_options)?;
+    let len = slice.len();
+    for record in slice {
+        buffer.push(record)?;
+    }
+    Ok(SliceBuffer {
+        buffer: buffer.end_writing()?,
+        written: len,
+        read: 0,
+    })
+}
+
+enum State<Record, CmpFn>
+where
+    CmpFn: Fn(&Record, &Record) -> Ordering,
+{
+    Buffering,
+    Reading {
+        slice_buffers: Vec<SliceBuffer>,
+        last_slice: Vec<Record>,
+        heads: BinaryHeap<SourcedRecord<Record>, MinSourcedRecordComparator<CmpFn>>,
+    },
+    Done,
+}
+
+struct SliceBuffer {
+    buffer: BufferReader,
+    written: usize,
+    read: usize,
+}
+
+impl<Record, CmpFn> State<Record, CmpFn>
+where
+    CmpFn: Fn(&Record, &Record) -> Ordering,
+{
+    fn start_reading<'de>(
+        mut slice_buffers: Vec<SliceBuffer>,
+        mut last_slice: Vec<Record>,
+        cmp: CmpFn,
+    ) -> Result<Self, bincode::Error>
+    where
+        Record: Deserialize<'de>,
+    {
+        if !last_slice.is_empty() {
+            // reverse is important
+            last_slice.sort_by(|r1, r2| cmp(r1, r2).reverse());
+        }
+        let slice_buffers_len = slice_buffers.len();
+        let heads = slice_buffers
+            .iter_mut()
+            .enumerate()
+            .map(
+                |(
+                    source_index,
+                    SliceBuffer {
+                        buffer,
+                        written,
+                        read,
+                    },
+                )| {
+                    assert_ne!(*written, 0);
+                    assert_eq!(*read, 0);
+                    let record: Record = buffer.read()?;
+                    (*read) = 1;
+                    Ok(Some(SourcedRecord {
+                        record,
                         source_index,
-                        SliceBuffer {
-                            buffer,
-                            written,
-                            read,
-                        },
-                    )| {
-                        assert_ne!(*written, 0);
-                        assert_eq!(*read, 0);
-                        let record: Record = buffer.read()?;
-                        (*read) = 1;
-                        Ok(Some(SourcedRecord {
+                    }))
+                },
+            )
+            .filter_map(Result::transpose)
+            .chain(
+                last_slice
+                    .pop()
+                    .map(|record| {
+                        Ok(SourcedRecord {
                             record,
-                            source_index,
-                        }))
-                    },
-                )
-                .filter_map(Result::transpose)
-                .chain(
-                    current_slice
-                        .pop()
-                        .map(|record| {
-                            Ok(SourcedRecord {
-                                record,
-                                source_index: slice_buffers_len,
-                            })
+                            source_index: slice_buffers_len,
                         })
-                        .into_iter(),
-                )
-                .collect::<Result<Vec<SourcedRecord<Record>>, _>>()?;
-            self.state = State::Reading {
-                slice_buffers,
-                last_slice: current_slice,
-                heads: BinaryHeap::from_vec_cmp(
-                    heads,
-                    MinSourcedRecordComparator {
-                        cmp: self.cmp.clone(),
-                    },
-                ),
-            }
-        }
-        match &mut self.state {
+                    })
+                    .into_iter(),
+            )
+            .collect::<Result<Vec<SourcedRecord<Record>>, bincode::Error>>()?;
+        Ok(State::Reading {
+            slice_buffers,
+            last_slice,
+            heads: BinaryHeap::from_vec_cmp(heads, MinSourcedRecordComparator { cmp }),
+        })
+    }
+
+    fn next_sorted<'de>(&mut self) -> Result<Option<Record>, bincode::Error>
+    where
+        Record: Deserialize<'de>,
+    {
+        match self {
             State::Buffering => {
                 unreachable!();
             }
@@ -163,8 +473,7 @@ where
                     }
                     Ok(Some(record))
                 } else {
-                    let mut state = State::Done;
-                    std::mem::swap(&mut self.state, &mut state);
+                    let state = std::mem::replace(self, State::Done);
                     state.fallible_drop()?;
                     Ok(None)
                 }
@@ -172,31 +481,7 @@ where
             State::Done => Ok(None),
         }
     }
-}
 
-enum State<Record, CmpFn>
-where
-    CmpFn: Fn(&Record, &Record) -> Ordering,
-{
-    Buffering,
-    Reading {
-        slice_buffers: Vec<SliceBuffer>,
-        last_slice: Vec<Record>,
-        heads: BinaryHeap<SourcedRecord<Record>, MinSourcedRecordComparator<CmpFn>>,
-    },
-    Done,
-}
-
-struct SliceBuffer {
-    buffer: BufferReader,
-    written: usize,
-    read: usize,
-}
-
-impl<Record, CmpFn> State<Record, CmpFn>
-where
-    CmpFn: Fn(&Record, &Record) -> Ordering,
-{
     fn fallible_drop(self) -> Result<(), bincode::Error> {
         match self {
             State::Reading {
@@ -411,3 +696,144 @@ fn should_sort_big_stream(#[case] slice_size: usize) {
     assert_matches!(stream.next(), Ok(None));
     assert_matches!(stream.next(), Ok(None));
 }
+
+#[cfg(test)]
+#[rstest]
+#[case(1, 1)]
+#[case(2, 3)]
+#[case(4, 42)]
+#[case(4, DEFAULT_SLICE_SIZE)]
+fn should_sort_big_stream_in_parallel(#[case] threads: usize, #[case] slice_size: usize) {
+    #[derive(Debug)]
+    enum Error {
+        Bincode(bincode::Error),
+    }
+
+    impl From<bincode::Error> for Error {
+        fn from(err: bincode::Error) -> Self {
+            Self::Bincode(err)
+        }
+    }
+
+    use rand::Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    let mut rng = rand_chacha::ChaCha8Rng::from_entropy();
+    println!("Seed: {:02x?}", rng.get_seed());
+
+    // Sorting on the high byte only checks that the sort is stable
+    let input = (0..(256 * 1024))
+        .map(|_| {
+            let num: u32 = rng.gen();
+            num
+        })
+        .collect::<Vec<_>>();
+
+    let mut stream = ParallelSort::with_slice_size(
+        fallible_iterator::convert(input.clone().into_iter().map(Ok::<_, Error>)),
+        |a: &u32, b: &u32| (a >> 24).cmp(&(b >> 24)),
+        threads,
+        slice_size,
+    );
+
+    let mut input = input;
+    input.sort_by_key(|num| num >> 24);
+    for num in input {
+        assert_matches!(stream.next(), Ok(Some(v)) if v == num);
+    }
+    // End of stream
+    assert_matches!(stream.next(), Ok(None));
+    assert_matches!(stream.next(), Ok(None));
+}
+
+#[test]
+#[should_panic(expected = "bad comparison")]
+fn should_resume_panic_of_sort_worker() {
+    let mut stream = ParallelSort::with_slice_size(
+        fallible_iterator::convert((0..100).map(Ok::<u32, bincode::Error>)),
+        |_: &u32, _: &u32| -> Ordering { panic!("bad comparison") },
+        2,
+        10,
+    );
+    stream.next().ok();
+}
+
+#[cfg(test)]
+#[rstest]
+fn should_sort_with_memory_budget(
+    #[values(None, Some(2))] threads: Option<usize>,
+    #[values(
+        BufferCompression::None,
+        BufferCompression::Lz4,
+        BufferCompression::Zstd
+    )]
+    compression: BufferCompression,
+) {
+    #[derive(Debug)]
+    enum Error {
+        Bincode(bincode::Error),
+    }
+
+    impl From<bincode::Error> for Error {
+        fn from(err: bincode::Error) -> Self {
+            Self::Bincode(err)
+        }
+    }
+
+    let spill_dir = tempfile::tempdir().unwrap();
+    let options = SortOptions {
+        memory_budget: Some(1024),
+        buffer: BufferOptions {
+            spill_dir: Some(spill_dir.path().to_path_buf()),
+            compression,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let input = (0..10_000)
+        .rev()
+        .map(|num| format!("{:05}", num))
+        .collect::<Vec<_>>();
+    let input_iter = fallible_iterator::convert(input.into_iter().map(Ok::<_, Error>));
+    let mut stream: Box<dyn FallibleIterator<Item = String, Error = Error>> =
+        if let Some(threads) = threads {
+            Box::new(ParallelSort::with_options(
+                input_iter,
+                |a: &String, b: &String| a.cmp(b),
+                threads,
+                options,
+            ))
+        } else {
+            Box::new(Sort::with_options(
+                input_iter,
+                |a: &String, b: &String| a.cmp(b),
+                options,
+            ))
+        };
+
+    for num in 0..10_000 {
+        assert_matches!(stream.next(), Ok(Some(v)) if v == format!("{:05}", num));
+    }
+    // End of stream
+    assert_matches!(stream.next(), Ok(None));
+}
+
+#[test]
+fn should_slice_by_memory_budget() {
+    let mut slicer = Slicer::new(
+        &SortOptions {
+            memory_budget: Some(20),
+            ..Default::default()
+        },
+        1,
+    );
+    // "abcd" is serialized in 5 bytes
+    let record = "abcd".to_string();
+    assert!(!slicer.push(&record).unwrap());
+    assert!(!slicer.push(&record).unwrap());
+    assert!(!slicer.push(&record).unwrap());
+    assert!(!slicer.push(&record).unwrap());
+    assert!(slicer.push(&record).unwrap());
+    assert!(!slicer.push(&record).unwrap());
+}
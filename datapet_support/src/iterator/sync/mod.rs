@@ -1 +1,4 @@
 pub mod mpsc;
+pub mod queue;
+#[cfg(feature = "tokio")]
+pub mod tokio;
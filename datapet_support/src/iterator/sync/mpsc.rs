@@ -1,19 +1,128 @@
 use fallible_iterator::FallibleIterator;
-use std::sync::mpsc::{Receiver, RecvError};
+use std::{
+    cell::RefCell,
+    sync::mpsc::{Receiver, RecvError, SendError, SyncSender},
+};
 
-/// Receives records from a `Receiver`
+/// The sending half of a channel between threads.
+pub trait ChannelSender<T> {
+    fn send(&self, value: T) -> Result<(), SendError<T>>;
+}
+
+impl<T> ChannelSender<T> for SyncSender<T> {
+    fn send(&self, value: T) -> Result<(), SendError<T>> {
+        SyncSender::send(self, value)
+    }
+}
+
+/// The receiving half of a channel between threads.
+pub trait ChannelReceiver<T> {
+    fn recv(&self) -> Result<T, RecvError>;
+}
+
+impl<T> ChannelReceiver<T> for Receiver<T> {
+    fn recv(&self) -> Result<T, RecvError> {
+        Receiver::recv(self)
+    }
+}
+
+/// Receives records one at a time, `None` marking the end of the stream.
+pub trait RecordReceiver<R> {
+    fn recv(&self) -> Result<Option<R>, RecvError>;
+}
+
+impl<R> RecordReceiver<R> for Receiver<Option<R>> {
+    fn recv(&self) -> Result<Option<R>, RecvError> {
+        Receiver::recv(self)
+    }
+}
+
+/// Sends records in batches through a channel.
+///
+/// Records are sent once `batch_size` of them are buffered. The end of the stream (`None`) flushes
+/// the pending records, then sends an empty batch.
+pub struct BatchSender<R, TX = SyncSender<Vec<R>>> {
+    tx: TX,
+    batch_size: usize,
+    batch: RefCell<Vec<R>>,
+}
+
+impl<R, TX: ChannelSender<Vec<R>>> BatchSender<R, TX> {
+    pub fn new(tx: TX, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be greater than 0");
+        Self {
+            tx,
+            batch_size,
+            batch: RefCell::new(Vec::with_capacity(batch_size)),
+        }
+    }
+
+    pub fn send(&self, record: Option<R>) -> Result<(), SendError<Vec<R>>> {
+        let mut batch = self.batch.borrow_mut();
+        if let Some(record) = record {
+            batch.push(record);
+            if batch.len() >= self.batch_size {
+                let full_batch =
+                    std::mem::replace(&mut *batch, Vec::with_capacity(self.batch_size));
+                self.tx.send(full_batch)?;
+            }
+        } else {
+            if !batch.is_empty() {
+                self.tx.send(std::mem::take(&mut *batch))?;
+            }
+            self.tx.send(Vec::new())?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives the records sent by a [`BatchSender`], one at a time.
+pub struct BatchReceiver<R, RX = Receiver<Vec<R>>> {
+    rx: RX,
+    batch: RefCell<std::vec::IntoIter<R>>,
+}
+
+impl<R, RX: ChannelReceiver<Vec<R>>> BatchReceiver<R, RX> {
+    pub fn new(rx: RX) -> Self {
+        Self {
+            rx,
+            batch: RefCell::new(Vec::new().into_iter()),
+        }
+    }
+
+    pub fn recv(&self) -> Result<Option<R>, RecvError> {
+        let mut batch = self.batch.borrow_mut();
+        if let Some(record) = batch.next() {
+            return Ok(Some(record));
+        }
+        // An empty batch marks the end of the stream
+        *batch = self.rx.recv()?.into_iter();
+        Ok(batch.next())
+    }
+}
+
+impl<R, RX: ChannelReceiver<Vec<R>>> RecordReceiver<R> for BatchReceiver<R, RX> {
+    fn recv(&self) -> Result<Option<R>, RecvError> {
+        BatchReceiver::recv(self)
+    }
+}
+
+/// Receives records from a `Receiver` or a [`BatchReceiver`]
 #[derive(new)]
-pub struct Receive<R, E> {
-    rx: Receiver<Option<R>>,
+pub struct Receive<R, E, RX = Receiver<Option<R>>> {
+    rx: RX,
+    #[new(default)]
+    _r: std::marker::PhantomData<R>,
     #[new(default)]
     _e: std::marker::PhantomData<E>,
     #[new(default)]
     end_of_input: bool,
 }
 
-impl<R, E> FallibleIterator for Receive<R, E>
+impl<R, E, RX> FallibleIterator for Receive<R, E, RX>
 where
     E: From<RecvError>,
+    RX: RecordReceiver<R>,
 {
     type Item = R;
     type Error = E;
@@ -84,3 +193,48 @@ fn should_stream_received_records_from_sync_channel() {
     assert_matches!(stream.next(), Ok(None));
     assert_matches!(stream.next(), Ok(None));
 }
+
+#[test]
+fn should_stream_records_received_in_batches() {
+    use std::sync::mpsc::sync_channel;
+
+    #[derive(Debug)]
+    struct Error(String);
+
+    impl From<RecvError> for Error {
+        fn from(_: RecvError) -> Self {
+            Self("Receive error".to_string())
+        }
+    }
+
+    let (tx, rx) = sync_channel(100);
+    let tx = BatchSender::new(tx, 10);
+    let mut stream = Receive::<_, Error, _>::new(BatchReceiver::new(rx));
+    for i in 0..42 {
+        tx.send(Some(i)).unwrap();
+    }
+    tx.send(None).unwrap();
+    for i in 0..42 {
+        assert_matches!(stream.next(), Ok(Some(j)) if j == i);
+    }
+    // End of stream
+    assert_matches!(stream.next(), Ok(None));
+    assert_matches!(stream.next(), Ok(None));
+}
+
+#[test]
+fn should_send_full_batches_then_flush_on_end_of_stream() {
+    use std::sync::mpsc::sync_channel;
+
+    let (tx, rx) = sync_channel(100);
+    let tx = BatchSender::new(tx, 4);
+    for i in 0..10 {
+        tx.send(Some(i)).unwrap();
+    }
+    assert_eq!(rx.try_recv(), Ok(vec![0, 1, 2, 3]));
+    assert_eq!(rx.try_recv(), Ok(vec![4, 5, 6, 7]));
+    assert!(rx.try_recv().is_err());
+    tx.send(None).unwrap();
+    assert_eq!(rx.try_recv(), Ok(vec![8, 9]));
+    assert_eq!(rx.try_recv(), Ok(vec![]));
+}
@@ -0,0 +1,174 @@
+//! Unbounded in-memory queues connecting the threads of a chain run on a single thread.
+//!
+//! The producing thread runs to completion before the consuming one starts, so receiving never
+//! waits: either a value is queued, or the producer is gone.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{RecvError, SendError},
+        Arc, Mutex,
+    },
+};
+
+use super::mpsc::{ChannelReceiver, ChannelSender, RecordReceiver};
+
+struct Queue<T> {
+    values: VecDeque<T>,
+    closed: bool,
+}
+
+/// Creates an unbounded queue.
+pub fn channel<T>() -> (QueueSender<T>, QueueReceiver<T>) {
+    let queue = Arc::new(Mutex::new(Queue {
+        values: VecDeque::new(),
+        closed: false,
+    }));
+    (
+        QueueSender {
+            queue: queue.clone(),
+        },
+        QueueReceiver { queue },
+    )
+}
+
+/// The sending half of a queue, the queue being closed when it is dropped.
+pub struct QueueSender<T> {
+    queue: Arc<Mutex<Queue<T>>>,
+}
+
+impl<T> QueueSender<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if Arc::strong_count(&self.queue) == 1 {
+            // The receiver is gone
+            return Err(SendError(value));
+        }
+        self.queue
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .values
+            .push_back(value);
+        Ok(())
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        self.queue
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .closed = true;
+    }
+}
+
+impl<T> ChannelSender<T> for QueueSender<T> {
+    fn send(&self, value: T) -> Result<(), SendError<T>> {
+        QueueSender::send(self, value)
+    }
+}
+
+/// The receiving half of a queue.
+pub struct QueueReceiver<T> {
+    queue: Arc<Mutex<Queue<T>>>,
+}
+
+impl<T> QueueReceiver<T> {
+    /// Takes the next value of the queue, failing once the queue is empty and closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queue is empty while the sender is still alive, which means the threads are
+    /// not run in the order of the data flow.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut queue = self.queue.lock().unwrap_or_else(|err| err.into_inner());
+        match queue.values.pop_front() {
+            Some(value) => Ok(value),
+            None if queue.closed => Err(RecvError),
+            None => panic!("receiving from an empty queue whose sender has not finished"),
+        }
+    }
+}
+
+impl<T> ChannelReceiver<T> for QueueReceiver<T> {
+    fn recv(&self) -> Result<T, RecvError> {
+        QueueReceiver::recv(self)
+    }
+}
+
+impl<R> RecordReceiver<R> for QueueReceiver<Option<R>> {
+    fn recv(&self) -> Result<Option<R>, RecvError> {
+        QueueReceiver::recv(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fallible_iterator::FallibleIterator;
+    use std::sync::mpsc::RecvError;
+
+    use super::channel;
+    use crate::iterator::sync::mpsc::{BatchReceiver, BatchSender, Receive};
+
+    #[derive(Debug)]
+    struct Error(String);
+
+    impl From<RecvError> for Error {
+        fn from(_: RecvError) -> Self {
+            Self("Receive error".to_string())
+        }
+    }
+
+    #[test]
+    fn should_stream_queued_records() {
+        let (tx, rx) = channel();
+        for i in 0..42 {
+            tx.send(Some(i)).unwrap();
+        }
+        tx.send(None).unwrap();
+        drop(tx);
+        let mut stream = Receive::<_, Error, _>::new(rx);
+        for i in 0..42 {
+            assert_matches!(stream.next(), Ok(Some(j)) if j == i);
+        }
+        // End of stream
+        assert_matches!(stream.next(), Ok(None));
+        assert_matches!(stream.next(), Ok(None));
+    }
+
+    #[test]
+    fn should_stream_queued_batches() {
+        let (tx, rx) = channel();
+        {
+            let tx = BatchSender::new(tx, 10);
+            for i in 0..42 {
+                tx.send(Some(i)).unwrap();
+            }
+            tx.send(None).unwrap();
+        }
+        let stream = Receive::<_, Error, _>::new(BatchReceiver::new(rx));
+        assert_eq!(
+            stream.collect::<Vec<_>>().unwrap(),
+            (0..42).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn should_fail_once_closed_or_disconnected() {
+        let (tx, rx) = channel::<u32>();
+        tx.send(1).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Err(RecvError));
+
+        let (tx, rx) = channel::<u32>();
+        drop(rx);
+        assert_eq!(tx.send(42).unwrap_err().0, 42);
+    }
+
+    #[test]
+    #[should_panic(expected = "sender has not finished")]
+    fn should_panic_when_sender_has_not_finished() {
+        let (_tx, rx) = channel::<u32>();
+        let _ = rx.recv();
+    }
+}
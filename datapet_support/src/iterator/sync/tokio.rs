@@ -0,0 +1,140 @@
+//! Bounded tokio channels used from blocking threads, for chains running on a tokio runtime.
+
+use std::{
+    cell::RefCell,
+    sync::mpsc::{RecvError, SendError},
+};
+
+use super::mpsc::{ChannelReceiver, ChannelSender, RecordReceiver};
+
+pub use tokio::task::spawn_blocking;
+
+/// Creates a bounded tokio channel to be used from blocking threads.
+///
+/// Unlike `std::sync::mpsc::sync_channel`, tokio channels cannot be rendezvous channels, a
+/// capacity of 0 is raised to 1.
+pub fn channel<T>(capacity: usize) -> (BlockingSender<T>, BlockingReceiver<T>) {
+    let (tx, rx) = tokio::sync::mpsc::channel(capacity.max(1));
+    (
+        BlockingSender { tx },
+        BlockingReceiver {
+            rx: RefCell::new(rx),
+        },
+    )
+}
+
+/// The sending half of a tokio channel, blocking until there is room in the channel.
+///
+/// It must not be used from an asynchronous context, but it can be from a
+/// [`spawn_blocking`] task.
+pub struct BlockingSender<T> {
+    tx: tokio::sync::mpsc::Sender<T>,
+}
+
+impl<T> BlockingSender<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.tx.blocking_send(value).map_err(|err| SendError(err.0))
+    }
+}
+
+impl<T> ChannelSender<T> for BlockingSender<T> {
+    fn send(&self, value: T) -> Result<(), SendError<T>> {
+        BlockingSender::send(self, value)
+    }
+}
+
+/// The receiving half of a tokio channel, blocking until a value is received.
+///
+/// It must not be used from an asynchronous context, but it can be from a
+/// [`spawn_blocking`] task.
+pub struct BlockingReceiver<T> {
+    rx: RefCell<tokio::sync::mpsc::Receiver<T>>,
+}
+
+impl<T> BlockingReceiver<T> {
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.rx.borrow_mut().blocking_recv().ok_or(RecvError)
+    }
+}
+
+impl<T> ChannelReceiver<T> for BlockingReceiver<T> {
+    fn recv(&self) -> Result<T, RecvError> {
+        BlockingReceiver::recv(self)
+    }
+}
+
+impl<R> RecordReceiver<R> for BlockingReceiver<Option<R>> {
+    fn recv(&self) -> Result<Option<R>, RecvError> {
+        BlockingReceiver::recv(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fallible_iterator::FallibleIterator;
+    use std::sync::mpsc::RecvError;
+
+    use super::{channel, spawn_blocking};
+    use crate::iterator::sync::mpsc::{BatchReceiver, BatchSender, Receive};
+
+    #[derive(Debug)]
+    struct Error(String);
+
+    impl From<RecvError> for Error {
+        fn from(_: RecvError) -> Self {
+            Self("Receive error".to_string())
+        }
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn should_stream_records_between_blocking_tasks() {
+        runtime().block_on(async {
+            let (tx, rx) = channel(0);
+            let producer = spawn_blocking(move || {
+                for i in 0..42 {
+                    tx.send(Some(i)).unwrap();
+                }
+                tx.send(None).unwrap();
+            });
+            let consumer = spawn_blocking(move || {
+                Receive::<_, Error, _>::new(rx).collect::<Vec<_>>().unwrap()
+            });
+            producer.await.unwrap();
+            assert_eq!(consumer.await.unwrap(), (0..42).collect::<Vec<_>>());
+        });
+    }
+
+    #[test]
+    fn should_stream_batches_between_blocking_tasks() {
+        runtime().block_on(async {
+            let (tx, rx) = channel(2);
+            let producer = spawn_blocking(move || {
+                let tx = BatchSender::new(tx, 10);
+                for i in 0..42 {
+                    tx.send(Some(i)).unwrap();
+                }
+                tx.send(None).unwrap();
+            });
+            let consumer = spawn_blocking(move || {
+                Receive::<_, Error, _>::new(BatchReceiver::new(rx))
+                    .collect::<Vec<_>>()
+                    .unwrap()
+            });
+            producer.await.unwrap();
+            assert_eq!(consumer.await.unwrap(), (0..42).collect::<Vec<_>>());
+        });
+    }
+
+    #[test]
+    fn should_fail_to_send_once_receiver_is_dropped() {
+        let (tx, rx) = channel::<u32>(1);
+        drop(rx);
+        assert_eq!(tx.send(42).unwrap_err().0, 42);
+    }
+}
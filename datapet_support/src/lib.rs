@@ -13,6 +13,8 @@ extern crate lazy_static;
 extern crate thiserror;
 
 pub use datapet_codegen_macro::{tracking_allocator_main, tracking_allocator_static};
+#[cfg(feature = "tracing")]
+pub use tracing;
 
 pub mod chain;
 pub mod data;
@@ -20,6 +22,10 @@ pub mod iterator;
 
 use std::sync::mpsc::{RecvError, SendError};
 
+use chain::{
+    cancellation::{ThreadFailure, ThreadFailureCause},
+    context::ErrorContext,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Error, Debug)]
@@ -32,6 +38,35 @@ pub enum DatapetError {
     PipeWrite,
     #[error("Bincode error {0}")]
     Bincode(#[from] bincode::Error),
+    #[error("Configuration error: {0}")]
+    Config(#[from] chain::configuration::ConfigError),
+    #[error("{0}")]
+    Interrupted(#[from] chain::cancellation::Interrupted),
+    #[error("{0}")]
+    FactViolation(#[from] chain::facts::FactViolation),
+    #[error("Thread {thread_id} ({name}) failed: {source}")]
+    ThreadFailed {
+        thread_id: usize,
+        name: &'static str,
+        source: Box<DatapetError>,
+    },
+    #[error("Thread {thread_id} ({name}) panicked: {payload}")]
+    ThreadPanicked {
+        thread_id: usize,
+        name: &'static str,
+        payload: String,
+    },
+    #[error(
+        "Filter {name} (thread {thread_id}{}) failed: {source}",
+        .record_index.map(|index| format!(", record {}", index)).unwrap_or_default()
+    )]
+    Context {
+        name: &'static str,
+        thread_id: usize,
+        /// The index of the record being processed when the error occurred, if known.
+        record_index: Option<usize>,
+        source: Box<DatapetError>,
+    },
 }
 
 impl DatapetError {
@@ -40,6 +75,40 @@ impl DatapetError {
     }
 }
 
+impl ErrorContext for DatapetError {
+    fn with_context(
+        self,
+        name: &'static str,
+        thread_id: usize,
+        record_index: Option<usize>,
+    ) -> Self {
+        match self {
+            Self::Context { .. } => self,
+            _ => Self::Context {
+                name,
+                thread_id,
+                record_index,
+                source: Box::new(self),
+            },
+        }
+    }
+
+    fn thread_failed(failure: ThreadFailure<Self>) -> Self {
+        match failure.cause {
+            ThreadFailureCause::Error(err) => Self::ThreadFailed {
+                thread_id: failure.thread_id,
+                name: failure.name,
+                source: Box::new(err),
+            },
+            ThreadFailureCause::Panic(payload) => Self::ThreadPanicked {
+                thread_id: failure.thread_id,
+                name: failure.name,
+                payload,
+            },
+        }
+    }
+}
+
 impl From<RecvError> for DatapetError {
     fn from(_: RecvError) -> Self {
         Self::PipeRead
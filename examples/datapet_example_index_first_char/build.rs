@@ -168,14 +168,24 @@ use super::tokenize;
         resolver
     };
 
+    let monitor = std::env::var_os("CARGO_FEATURE_DTPT_MONITOR").is_some();
+
     let graph = dtpt_main(GraphBuilder::new(
         &type_resolver,
-        ChainCustomizer::default(),
+        ChainCustomizer {
+            stream_metrics: monitor,
+            memory_groups: monitor,
+            ..Default::default()
+        },
     ))
     .unwrap_or_else(|err| {
         panic!("{}", err);
     });
 
+    for warning in graph.warnings() {
+        println!("cargo:warning={}", warning);
+    }
+
     let out_dir = std::env::var("OUT_DIR").unwrap();
     graph.generate(Path::new(&out_dir)).unwrap();
 }
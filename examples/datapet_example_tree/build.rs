@@ -29,6 +29,11 @@ use datapet::{
 )
 { ( datapet::filter::monitor::monitor() ) }
 
+#(
+    config: [
+        (name: "root", type: "String", default: "datapet", doc: "The directory to walk"),
+    ],
+)
 {
   (
       function_produce#read_fs(
@@ -43,7 +48,7 @@ use datapet::{
 
             let mut full_name_index = BTreeMap::<PathBuf, usize>::new();
 
-            for (id, entry) in WalkDir::new(thread_control.chain_configuration.variables["root"].clone()).into_iter().enumerate() {
+            for (id, entry) in WalkDir::new(&thread_control.config.root).into_iter().enumerate() {
                 let entry = entry.map_err(|err| DatapetError::Custom(err.to_string()))?;
 
                 let parent_id = entry.path().parent()
@@ -108,14 +113,26 @@ use datapet::{
         resolver
     };
 
+    let monitor = std::env::var_os("CARGO_FEATURE_DTPT_MONITOR").is_some();
+
     let graph = dtpt_main(GraphBuilder::new(
         &type_resolver,
-        ChainCustomizer::default(),
+        ChainCustomizer {
+            // Walking a large tree can take a while
+            handle_signals: true,
+            stream_metrics: monitor,
+            memory_groups: monitor,
+            ..Default::default()
+        },
     ))
     .unwrap_or_else(|err| {
         panic!("{}", err);
     });
 
+    for warning in graph.warnings() {
+        println!("cargo:warning={}", warning);
+    }
+
     let out_dir = std::env::var("OUT_DIR").unwrap();
     graph.generate(Path::new(&out_dir)).unwrap();
 }
@@ -1,4 +1,4 @@
-use datapet_support::chain::configuration::ChainConfiguration;
+use datapet_support::{chain::configuration::ChainConfiguration, DatapetError};
 
 #[macro_use]
 extern crate static_assertions;
@@ -16,14 +16,17 @@ datapet_support::tracking_allocator_static!();
 fn main() {
     let mut chain_configuration = ChainConfiguration::new();
 
-    let mut args = std::env::args();
-    let root = args
-        .next()
-        .and_then(|_| args.next())
-        .unwrap_or_else(|| "datapet".to_owned());
-    chain_configuration
-        .variables
-        .insert("root".to_owned(), root);
+    if let Some(root) = std::env::args().nth(1) {
+        chain_configuration
+            .variables
+            .insert("root".to_owned(), root);
+    }
 
-    chain::main(chain_configuration).unwrap();
+    match chain::main(chain_configuration) {
+        Err(DatapetError::Interrupted(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(130);
+        }
+        result => result.unwrap(),
+    }
 }
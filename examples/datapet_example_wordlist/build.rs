@@ -9,11 +9,8 @@ fn main() {
         r###"
 use datapet::{
     filter::{
-        anchor::anchor, dedup::dedup, hof::index::wordlist::build_word_list,
-        function::{
-            produce::function_produce,
-            terminate::function_terminate,
-        },
+        anchor::anchor, dedup::dedup, dot::write_dot, hof::index::wordlist::build_word_list,
+        function::produce::function_produce,
         sort::sort,
     },
 };
@@ -57,83 +54,23 @@ use datapet::{
         ci_anchor_field: "ci_anchor",
         ci_refs_field: "ci_refs",
       ) [s2, s3, s4]
-    - [s2, s3, s4] function_terminate#dot(
-        body: r#"
-            println!("digraph wordlist \{{");
-            let mut input_0 = Some(input_0);
-            let mut input_1 = Some(input_1);
-            let mut input_2 = Some(input_2);
-            let mut input_3 = Some(input_3);
-            while input_0.is_some() || input_1.is_some() || input_2.is_some() || input_3.is_some() {
-                let mut read = 0;
-                if let Some(input) = &input_0 {
-                    let mut closed = false;
-                    for record in input.try_iter() {
-                        if let Some(record) = record {
-                            println!("word_{} [label=\"{}\"]", record.anchor(), record.token());
-                            read += 1;
-                        } else {
-                            closed = true;
-                        }
-                    }
-                    if closed {
-                        input_0 = None;
-                    }
-                }
-                if let Some(input) = &input_1 {
-                    let mut closed = false;
-                    for record in input.try_iter() {
-                        if let Some(record) = record {
-                            println!("rev_word_{} [label=\"{}\"]", record.anchor(), record.token());
-                            println!("word_{} -> rev_word_{}", record.anchor(), record.anchor());
-                            read += 1;
-                        } else {
-                            closed = true;
-                        }
-                    }
-                    if closed {
-                        input_1 = None;
-                    }
-                }
-                if let Some(input) = &input_2 {
-                    let mut closed = false;
-                    for record in input.try_iter() {
-                        if let Some(record) = record {
-                            println!("ci_word_{} [label=\"{}\"]", record.ci_anchor(), record.token());
-                            for ref_record in record.ci_refs() {
-                                println!("word_{} -> ci_word_{}", ref_record.anchor(), record.ci_anchor());
-                            }
-                            read += 1;
-                        } else {
-                            closed = true;
-                        }
-                    }
-                    if closed {
-                        input_2 = None;
-                    }
-                }
-                if let Some(input) = &input_3 {
-                    let mut closed = false;
-                    for record in input.try_iter() {
-                        if let Some(record) = record {
-                            println!("rev_ci_word_{} [label=\"{}\"]", record.ci_anchor(), record.token());
-                            println!("ci_word_{} -> rev_ci_word_{}", record.ci_anchor(), record.ci_anchor());
-                            read += 1;
-                        } else {
-                            closed = true;
-                        }
-                    }
-                    if closed {
-                        input_3 = None;
-                    }
-                }
-                if read == 0 {
-                    std::thread::sleep(std::time::Duration::from_millis(42));
-                }
-            }
-            println!("}}");
-            Ok(())
-"#,
+    - [s2, s3, s4] write_dot#dot(
+        name: "wordlist",
+        inputs: [
+            [Nodes(id_field: "anchor", id_prefix: "word_", label_field: "token")],
+            [
+                Nodes(id_field: "anchor", id_prefix: "rev_word_", label_field: "token"),
+                Edges(from_field: "anchor", from_prefix: "word_", to_field: "anchor", to_prefix: "rev_word_"),
+            ],
+            [
+                Nodes(id_field: "ci_anchor", id_prefix: "ci_word_", label_field: "token"),
+                Edges(from_field: "ci_refs.anchor", from_prefix: "word_", to_field: "ci_anchor", to_prefix: "ci_word_"),
+            ],
+            [
+                Nodes(id_field: "ci_anchor", id_prefix: "rev_ci_word_", label_field: "token"),
+                Edges(from_field: "ci_anchor", from_prefix: "ci_word_", to_field: "ci_anchor", to_prefix: "rev_ci_word_"),
+            ],
+        ],
       )
   )
 }
@@ -147,14 +84,24 @@ use datapet::{
         resolver
     };
 
+    let monitor = std::env::var_os("CARGO_FEATURE_DTPT_MONITOR").is_some();
+
     let graph = dtpt_main(GraphBuilder::new(
         &type_resolver,
-        ChainCustomizer::default(),
+        ChainCustomizer {
+            stream_metrics: monitor,
+            memory_groups: monitor,
+            ..Default::default()
+        },
     ))
     .unwrap_or_else(|err| {
         panic!("{}", err);
     });
 
+    for warning in graph.warnings() {
+        println!("cargo:warning={}", warning);
+    }
+
     let out_dir = std::env::var("OUT_DIR").unwrap();
     graph.generate(Path::new(&out_dir)).unwrap();
 }
@@ -1,6 +1,8 @@
 use std::path::Path;
 
 fn main() {
+    println!("cargo:rerun-if-env-changed=DATAPET_TESTS_SEQUENTIAL");
+    println!("cargo:rerun-if-env-changed=DATAPET_TESTS_METRICS");
     let out_dir = std::env::var("OUT_DIR").unwrap();
     datapet_tests_source::generate_tests(Path::new(&out_dir));
 }
@@ -9,3 +9,345 @@ extern crate static_assertions;
 mod all_chains {
     include!(concat!(env!("OUT_DIR"), "/all_chains.rs"));
 }
+
+#[allow(dead_code)]
+#[allow(clippy::borrowed_box)]
+#[allow(clippy::module_inception)]
+mod round_trips {
+    include!(concat!(env!("OUT_DIR"), "/round_trips/all_chains.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use datapet_support::{
+        chain::{
+            configuration::{ChainConfiguration, ConfigError},
+            facts::FactViolation,
+        },
+        DatapetError,
+    };
+    use fallible_iterator::FallibleIterator;
+
+    use crate::all_chains::chain_setup::external_io::{
+        external::numbers::{Record, UnpackedRecord},
+        start, ExternalInputs,
+    };
+
+    #[test]
+    fn should_run_chain_with_external_io() {
+        let numbers = (0..1024_u32)
+            .rev()
+            .map(|num| Ok::<_, DatapetError>(Record::new(UnpackedRecord { num })));
+        let external_inputs = ExternalInputs {
+            numbers: Box::new(fallible_iterator::convert(numbers)),
+        };
+
+        let (mut external_outputs, run) = start(ChainConfiguration::default(), external_inputs);
+
+        let mut expected = 0;
+        while let Some(record) = external_outputs.sorted.next().unwrap() {
+            assert_eq!(expected, *record.num());
+            expected += 1;
+        }
+        assert_eq!(1024, expected);
+
+        run.wait().unwrap();
+    }
+
+    #[test]
+    fn should_report_invalid_config_before_running() {
+        use crate::all_chains::chain_setup::config::{configuration_from_args, main, Config};
+
+        let configuration = configuration_from_args(["--count=4".to_owned()]).unwrap();
+        let config = Config::from_configuration(&configuration).unwrap();
+        assert_eq!(4, config.count);
+        assert_eq!("record", config.label);
+
+        let configuration = configuration_from_args(["--count=many".to_owned()]).unwrap();
+        assert!(matches!(
+            main(configuration),
+            Err(DatapetError::Config(ConfigError::Invalid { name, .. })) if name == "count"
+        ));
+    }
+
+    #[test]
+    fn should_report_broken_order_fact() {
+        use crate::all_chains::chain_setup::facts::{configuration_from_args, main};
+
+        let configuration = configuration_from_args(["--swap=true".to_owned()]).unwrap();
+        match main(configuration) {
+            Err(DatapetError::ThreadFailed { source, .. }) => assert!(matches!(
+                *source,
+                DatapetError::FactViolation(FactViolation {
+                    fact: "order",
+                    record_index: 4,
+                    ..
+                })
+            )),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn should_record_stream_metrics() {
+        use crate::all_chains::metered::update::main;
+
+        let configuration = ChainConfiguration::default();
+        let metrics = configuration.metrics.clone();
+        main(configuration).unwrap();
+
+        let streams = metrics.snapshot();
+        assert_eq!(100, streams["double"].records_produced);
+        assert!(streams
+            .values()
+            .any(|stream| stream.records_sent == 100 && stream.records_received == 100));
+        assert!(streams.values().all(|stream| stream.queued == 0));
+    }
+
+    fn check_written_groups(
+        schema: &arrow::datatypes::Schema,
+        batches: Vec<arrow::record_batch::RecordBatch>,
+    ) {
+        use arrow::{
+            array::{Array, Float64Array, ListArray, StringArray, StructArray, UInt8Array},
+            datatypes::DataType,
+        };
+
+        assert_eq!(2, schema.fields().len());
+        assert_eq!("lsb2", schema.field(0).name());
+        assert_eq!(&DataType::UInt8, schema.field(0).data_type());
+        assert_eq!("group", schema.field(1).name());
+        match schema.field(1).data_type() {
+            DataType::List(item) => match item.data_type() {
+                DataType::Struct(fields) => assert_eq!(
+                    vec!["num", "label", "half"],
+                    fields.iter().map(|field| field.name()).collect::<Vec<_>>()
+                ),
+                other => panic!("unexpected item type {:?}", other),
+            },
+            other => panic!("unexpected group type {:?}", other),
+        }
+
+        // 4 groups written in batches of 3
+        assert_eq!(
+            vec![3, 1],
+            batches
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>()
+        );
+        let mut expected_lsb2 = 0;
+        for batch in &batches {
+            let lsb2 = batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<UInt8Array>()
+                .unwrap();
+            let group = batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<ListArray>()
+                .unwrap();
+            for row in 0..batch.num_rows() {
+                assert_eq!(expected_lsb2, lsb2.value(row));
+                let sub_records = group.value(row);
+                let sub_records = sub_records.as_any().downcast_ref::<StructArray>().unwrap();
+                assert_eq!(64, sub_records.len());
+                let num = sub_records
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<UInt8Array>()
+                    .unwrap();
+                let label = sub_records
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap();
+                let half = sub_records
+                    .column(2)
+                    .as_any()
+                    .downcast_ref::<Float64Array>()
+                    .unwrap();
+                for index in 0..sub_records.len() {
+                    let num = num.value(index);
+                    assert_eq!(expected_lsb2, num & 0x03);
+                    assert_eq!(format!("n{}", num), label.value(index));
+                    if num % 2 == 1 {
+                        assert_eq!(num as f64 * 0.5, half.value(index));
+                    } else {
+                        assert!(half.is_null(index));
+                    }
+                }
+                expected_lsb2 += 1;
+            }
+        }
+        assert_eq!(4, expected_lsb2);
+    }
+
+    #[test]
+    fn should_read_back_written_arrow_ipc() {
+        use crate::round_trips::arrow::write_arrow_ipc;
+
+        let path = std::env::temp_dir().join(format!(
+            "datapet_tests_write_arrow_ipc_{}.arrow",
+            std::process::id()
+        ));
+
+        let mut configuration = ChainConfiguration::default();
+        configuration.paths.insert("write".to_owned(), path.clone());
+        write_arrow_ipc::main(configuration).unwrap();
+
+        let reader =
+            arrow::ipc::reader::FileReader::try_new(std::fs::File::open(&path).unwrap(), None)
+                .unwrap();
+        let schema = reader.schema();
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        check_written_groups(&schema, batches);
+    }
+
+    #[test]
+    fn should_read_back_written_parquet() {
+        use crate::round_trips::arrow::write_parquet;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let path = std::env::temp_dir().join(format!(
+            "datapet_tests_write_parquet_{}.parquet",
+            std::process::id()
+        ));
+
+        let mut configuration = ChainConfiguration::default();
+        configuration.paths.insert("write".to_owned(), path.clone());
+        write_parquet::main(configuration).unwrap();
+
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap();
+        let schema = builder.schema().clone();
+        let batches = builder
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        check_written_groups(&schema, batches);
+    }
+
+    #[test]
+    fn should_write_dot_graph() {
+        use crate::round_trips::dot::write_dot;
+
+        let path = std::env::temp_dir().join(format!(
+            "datapet_tests_write_dot_{}.dot",
+            std::process::id()
+        ));
+
+        let mut configuration = ChainConfiguration::default();
+        configuration.paths.insert("write".to_owned(), path.clone());
+        write_dot::main(configuration).unwrap();
+
+        let dot = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines = dot.lines().collect::<Vec<_>>();
+        assert_eq!(Some(&r#"digraph "write_dot_test" {"#), lines.first());
+        assert_eq!(Some(&"}"), lines.last());
+        // Both inputs are written as soon as their records arrive, each one in order
+        let nodes = lines
+            .iter()
+            .filter(|line| line.contains("[label="))
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            (0..16)
+                .map(|num| format!(r#""num_{}" [label="node \"{}\""]"#, num, num))
+                .collect::<Vec<_>>(),
+            nodes
+        );
+        let mut edges = lines
+            .iter()
+            .filter(|line| line.contains(" -> "))
+            .copied()
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        let mut expected_edges = (0..16)
+            .map(|num| format!(r#""num_{}" -> "num_{}""#, num & 0x03, num))
+            .collect::<Vec<_>>();
+        expected_edges.sort_unstable();
+        assert_eq!(expected_edges, edges);
+        assert_eq!(2 + 16 + 16, lines.len());
+    }
+
+    #[test]
+    fn should_write_compressed_dot_graph() {
+        use crate::round_trips::dot::write_dot;
+        use datapet_support::iterator::io::compression::{self, Compression};
+        use std::io::Read;
+
+        let path = std::env::temp_dir().join(format!(
+            "datapet_tests_write_dot_{}.dot.gz",
+            std::process::id()
+        ));
+
+        let mut configuration = ChainConfiguration::default();
+        configuration.paths.insert("write".to_owned(), path.clone());
+        write_dot::main(configuration).unwrap();
+
+        // Compressed according to the extension
+        let header = std::fs::read(&path).unwrap();
+        assert_eq!(Compression::Gzip, Compression::from_magic_bytes(&header));
+        let mut dot = String::new();
+        compression::open(&path, None)
+            .unwrap()
+            .read_to_string(&mut dot)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines = dot.lines().collect::<Vec<_>>();
+        assert_eq!(Some(&r#"digraph "write_dot_test" {"#), lines.first());
+        assert_eq!(Some(&"}"), lines.last());
+        assert_eq!(2 + 16 + 16, lines.len());
+    }
+
+    #[test]
+    fn should_read_back_written_sqlite() {
+        use crate::{all_chains::sqlite::write_sqlite, round_trips::sqlite::read_written_sqlite};
+
+        let path = std::env::temp_dir().join(format!(
+            "datapet_tests_write_sqlite_{}.db",
+            std::process::id()
+        ));
+
+        let mut configuration = ChainConfiguration::default();
+        configuration.paths.insert("write".to_owned(), path.clone());
+        write_sqlite::main(configuration).unwrap();
+
+        let mut configuration = ChainConfiguration::default();
+        configuration.paths.insert("read".to_owned(), path.clone());
+        let result = read_written_sqlite::main(configuration);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn should_stop_endless_chain_on_signal() {
+        use crate::round_trips::signals::endless::main;
+
+        assert!(matches!(
+            main(ChainConfiguration::default()),
+            Err(DatapetError::Interrupted(_))
+        ));
+    }
+
+    #[test]
+    fn should_run_chain_on_tokio_runtime() {
+        use crate::round_trips::tokio::sort_join::main;
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime
+            .block_on(main(ChainConfiguration::default()))
+            .unwrap();
+    }
+}
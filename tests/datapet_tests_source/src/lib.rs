@@ -4,24 +4,75 @@ use truc::record::type_resolver::{StaticTypeResolver, TypeResolver};
 
 dtpt!(include_glob_test("dtpt_tests", "**/*.dtpt"));
 
+/// Chains run by the integration tests only, either with the paths of the files they write or read
+/// back set in their configuration, because they run until interrupted, or because their main is
+/// async.
+pub mod round_trips {
+    use datapet::{dtpt, prelude::*};
+    use std::{fs::File, io::Write, path::Path};
+    use truc::record::type_resolver::TypeResolver;
+
+    dtpt!(include_glob("dtpt_round_trips", "**/*.dtpt"));
+}
+
 pub fn generate_tests(out_dir: &Path) {
     let type_resolver = {
         let mut resolver = StaticTypeResolver::new();
         resolver.add_std_types();
         resolver
     };
+    let type_resolver = &type_resolver;
+
+    // Running the test chains on a single thread makes them reproducible and easier to debug
+    let sequential = std::env::var_os("DATAPET_TESTS_SEQUENTIAL").is_some();
+
+    // Instrumenting the test chains checks that the metered channels fit all the filters
+    let stream_metrics = std::env::var_os("DATAPET_TESTS_METRICS").is_some();
+
+    let new_graph_builder = |root: &'static str| {
+        move |module_path: &[&str]| {
+            let module_name = FullyQualifiedName::new_n(&["crate", root]).sub_n(module_path);
+            let streams_module_name = module_name.sub("streams");
+            let customizer = ChainCustomizer {
+                streams_module_name,
+                module_name,
+                // The test chains under tokio always have an async main, the test chains under
+                // sequential always run on a single thread
+                runtime: if module_path.contains(&"tokio") {
+                    ChainRuntime::Tokio
+                } else if sequential || module_path.contains(&"sequential") {
+                    ChainRuntime::Sequential
+                } else {
+                    ChainRuntime::Threads
+                },
+                // The test chains under batched send 7 records at a time through their pipes,
+                // leaving partial batches behind
+                pipe_batch_size: module_path.contains(&"batched").then_some(7),
+                // The test chains under signals run until they raise a signal
+                handle_signals: module_path.contains(&"signals"),
+                // The test chains under metered always record their stream metrics
+                stream_metrics: stream_metrics || module_path.contains(&"metered"),
+                // The test chains under traced run in tracing spans
+                tracing: module_path.contains(&"traced"),
+                // The test chains check their facts in release builds as well
+                facts_check: ChainFactsCheck::Always,
+                ..Default::default()
+            };
+            let mut graph = GraphBuilder::new(type_resolver, customizer);
+            // The test chains under auto_order rely on the sorts and dedups it inserts
+            graph.set_auto_order(module_path.contains(&"auto_order"));
+            graph
+        }
+    };
 
-    dtpt_generate_deep(out_dir, |module_path| {
-        let module_name = FullyQualifiedName::new_n(&["crate", "all_chains"]).sub_n(module_path);
-        let streams_module_name = module_name.sub("streams");
-        let customizer = ChainCustomizer {
-            streams_module_name,
-            module_name,
-            ..Default::default()
-        };
-        GraphBuilder::new(&type_resolver, customizer)
-    })
-    .unwrap_or_else(|err| {
+    dtpt_generate_deep(out_dir, new_graph_builder("all_chains")).unwrap_or_else(|err| {
         panic!("{}", err);
     });
+
+    let round_trips_out_dir = out_dir.join("round_trips");
+    std::fs::create_dir_all(&round_trips_out_dir).unwrap();
+    round_trips::dtpt_generate_deep(&round_trips_out_dir, new_graph_builder("round_trips"))
+        .unwrap_or_else(|err| {
+            panic!("{}", err);
+        });
 }